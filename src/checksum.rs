@@ -0,0 +1,245 @@
+use std::io;
+
+use crate::{mode::Mode, snes::{self, SnesMap}};
+
+/// A checksum stored in a ROM header next to the value recomputed from the ROM's data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checksum {
+    pub name: &'static str,
+    pub stored: u16,
+    pub computed: u16,
+}
+
+impl Checksum {
+    pub fn is_valid(&self) -> bool {
+        self.stored == self.computed
+    }
+}
+
+/// Every checksum `rom` carries for `mode`; empty when the system doesn't
+/// keep one or the file is too short to hold its header
+pub fn checksums(mode: Mode, rom: &[u8], snes_map: SnesMap) -> io::Result<Vec<Checksum>> {
+    Ok(match mode {
+        Mode::SuperNintendo => snes(rom, &snes::Layout::detect(rom, snes_map)?).into_iter().collect(),
+        Mode::Genesis => genesis(rom).into_iter().collect(),
+        Mode::GameBoy => gameboy(rom),
+        Mode::Nintendo | Mode::GameGear | Mode::MasterSystem => Vec::new(),
+    })
+}
+
+/// Offset of the SNES internal header for `layout`
+pub fn snes_header(layout: &snes::Layout) -> u64 {
+    layout.header + match layout.map {
+        SnesMap::HiRom => 0xFFC0,
+        SnesMap::ExHiRom => 0x40_FFC0,
+        SnesMap::LoRom | SnesMap::Auto => 0x7FC0,
+    }
+}
+
+/// 16-bit sum of every ROM byte, with the tail of non power of two ROMs
+/// mirrored up to the next power of two the way the cartridge sees it
+pub fn snes_sum(data: &[u8]) -> u16 {
+    let sum = |bytes: &[u8]| bytes.iter().fold(0_u32, |sum, &b| sum.wrapping_add(b as u32));
+    if data.is_empty() || data.len().is_power_of_two() { return sum(data) as u16; }
+
+    let base = 1 << (usize::BITS - 1 - data.len().leading_zeros());
+    let (head, tail) = data.split_at(base);
+    let repeat = (base / tail.len().next_power_of_two()) as u32;
+    sum(head).wrapping_add(sum(tail).wrapping_mul(repeat)) as u16
+}
+
+pub fn snes(rom: &[u8], layout: &snes::Layout) -> Option<Checksum> {
+    let header = snes_header(layout) as usize;
+    let stored = rom.get(header + 0x1E..header + 0x20)?;
+    let data = &rom[layout.header as usize..];
+
+    Some(Checksum { name: "SNES checksum", stored: u16::from_le_bytes([stored[0], stored[1]]), computed: snes_sum(data) })
+}
+
+/// 16-bit sum of the big endian words from 0x200 onward
+pub fn genesis_sum(rom: &[u8]) -> u16 {
+    rom.get(0x200..).unwrap_or(&[]).chunks(2).fold(0_u16, |sum, word| {
+        sum.wrapping_add(u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]))
+    })
+}
+
+pub fn genesis(rom: &[u8]) -> Option<Checksum> {
+    let stored = rom.get(0x18E..0x190)?;
+    Some(Checksum { name: "Genesis checksum", stored: u16::from_be_bytes([stored[0], stored[1]]), computed: genesis_sum(rom) })
+}
+
+/// The header checksum over 0x134-0x14C that the boot ROM verifies
+pub fn gameboy_header_sum(rom: &[u8]) -> u8 {
+    rom[0x134..0x14D].iter().fold(0_u8, |sum, &b| sum.wrapping_sub(b).wrapping_sub(1))
+}
+
+/// 16-bit sum of every byte except the global checksum itself
+pub fn gameboy_global_sum(rom: &[u8]) -> u16 {
+    rom.iter().enumerate()
+        .filter(|&(i, _)| i != 0x14E && i != 0x14F)
+        .fold(0_u16, |sum, (_, &b)| sum.wrapping_add(b as u16))
+}
+
+pub fn gameboy(rom: &[u8]) -> Vec<Checksum> {
+    if rom.len() < 0x150 { return Vec::new(); }
+
+    vec![
+        Checksum { name: "Game Boy header checksum", stored: rom[0x14D] as u16, computed: gameboy_header_sum(rom) as u16 },
+        Checksum { name: "Game Boy global checksum", stored: u16::from_be_bytes([rom[0x14E], rom[0x14F]]), computed: gameboy_global_sum(rom) },
+    ]
+}
+
+/// Rewrite every checksum `rom` carries for `mode` to match its contents,
+/// returning each with the value it was stored as before and the one now
+/// written. Empty when the system keeps no checksum
+pub fn fix_checksums(mode: Mode, rom: &mut [u8], snes_map: SnesMap) -> io::Result<Vec<Checksum>> {
+    let mut fixed = Vec::new();
+    match mode {
+        Mode::SuperNintendo => {
+            let layout = snes::Layout::detect(&*rom, snes_map)?;
+            let header = snes_header(&layout) as usize;
+            let Some(old) = snes(rom, &layout) else { return Ok(fixed) };
+            // a checksum and its complement always add 0x1FE to the sum, so write a
+            // matching pair first and the sum comes out the same with the real one in
+            rom[header + 0x1C..header + 0x20].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+            let sum = snes_sum(&rom[layout.header as usize..]);
+            rom[header + 0x1C..header + 0x1E].copy_from_slice(&(!sum).to_le_bytes());
+            rom[header + 0x1E..header + 0x20].copy_from_slice(&sum.to_le_bytes());
+            fixed.push(Checksum { computed: sum, ..old });
+        },
+        Mode::Genesis => {
+            let Some(old) = genesis(rom) else { return Ok(fixed) };
+            rom[0x18E..0x190].copy_from_slice(&old.computed.to_be_bytes());
+            fixed.push(old);
+        },
+        Mode::GameBoy => {
+            if rom.len() < 0x150 { return Ok(fixed); }
+            // the header checksum is part of what the global one sums, so it goes first
+            let [header, _] = gameboy(rom)[..] else { unreachable!() };
+            rom[0x14D] = header.computed as u8;
+            let [_, global] = gameboy(rom)[..] else { unreachable!() };
+            rom[0x14E..0x150].copy_from_slice(&global.computed.to_be_bytes());
+            fixed.extend([header, global]);
+        },
+        Mode::Nintendo | Mode::GameGear | Mode::MasterSystem => {},
+    }
+    Ok(fixed)
+}
+
+/// The ROM size a header declares next to the size of the ROM data actually there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeclaredSize {
+    pub name: &'static str,
+    pub declared: u64,
+    pub actual: u64,
+    /// Whether the header rounds the size up to a power of two, as SNES headers do
+    pub rounded: bool,
+}
+
+impl DeclaredSize {
+    pub fn is_valid(&self) -> bool {
+        match self.rounded {
+            true => self.actual > self.declared / 2 && self.actual <= self.declared,
+            false => self.actual == self.declared,
+        }
+    }
+}
+
+/// The size field `rom`'s header holds for `mode`, if the system has one and
+/// the file is long enough to hold it
+pub fn declared_size(mode: Mode, rom: &[u8], snes_map: SnesMap) -> io::Result<Option<DeclaredSize>> {
+    Ok(match mode {
+        Mode::SuperNintendo => {
+            let layout = snes::Layout::detect(rom, snes_map)?;
+            // 1KB shifted left by the byte at 0x7FD7 (LoROM), 0xFFD7 (HiROM)
+            rom.get(snes_header(&layout) as usize + 0x17).filter(|&&shift| shift < 32).map(|&shift| DeclaredSize {
+                name: "SNES ROM size", declared: 0x400 << shift, actual: rom.len() as u64 - layout.header, rounded: true,
+            })
+        },
+        // the address of the last ROM byte, at 0x1A4
+        Mode::Genesis => rom.get(0x1A4..0x1A8).map(|end| DeclaredSize {
+            name: "Genesis ROM end", declared: u32::from_be_bytes([end[0], end[1], end[2], end[3]]) as u64 + 1, actual: rom.len() as u64, rounded: false,
+        }),
+        Mode::GameBoy => rom.get(0x148).and_then(|&code| match code {
+            0x00..=0x08 => Some(0x8000 << code),
+            0x52 => Some(0x12_0000),
+            0x53 => Some(0x14_0000),
+            0x54 => Some(0x18_0000),
+            _ => None,
+        }).map(|declared| DeclaredSize { name: "Game Boy ROM size", declared, actual: rom.len() as u64, rounded: false }),
+        Mode::Nintendo | Mode::GameGear | Mode::MasterSystem => None,
+    })
+}
+
+// the reflected CRC-32 polynomial zip and most ROM databases use
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 of `data`, as zip archives and ROM databases (No-Intro, GoodTools) list it
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0_u32, |crc, &b| CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{locate::{apply_codes, Locator}, patch::ApplyStatus, testing::Rng};
+
+    // a random `size` byte ROM with valid checksums is patched with `code`, fixed, and checked again
+    fn round_trip(mode: Mode, size: usize, snes_map: SnesMap, code: &str) {
+        let mut rng = Rng::new(size as u64 ^ mode as u64);
+        let mut rom: Vec<u8> = (0..size).map(|_| rng.byte()).collect();
+        fix_checksums(mode, &mut rom, snes_map).unwrap();
+        assert!(checksums(mode, &rom, snes_map).unwrap().iter().all(Checksum::is_valid), "{mode} {size:#X}");
+
+        let results = apply_codes(&mut Locator::new(mode, snes_map, None), &mut rom, &[code], true).unwrap();
+        assert!(results.iter().all(|result| result.status == ApplyStatus::Applied), "{code}: {results:?}");
+        let fixed = fix_checksums(mode, &mut rom, snes_map).unwrap();
+        let verified = checksums(mode, &rom, snes_map).unwrap();
+        assert!(!verified.is_empty() && verified.iter().all(Checksum::is_valid), "{mode} {size:#X}: {verified:?}");
+        assert_eq!(fixed.iter().map(|checksum| checksum.computed).collect::<Vec<_>>(), verified.iter().map(|checksum| checksum.stored).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn fixed_snes_checksums_verify() {
+        let code = |address| crate::snes::encode_snes(&crate::patch::Patch { address, value: vec![0xA5], compare: None }).unwrap();
+        round_trip(Mode::SuperNintendo, 0x2_0000, SnesMap::LoRom, &code(0x01_9000));
+        round_trip(Mode::SuperNintendo, 0x2_0000, SnesMap::HiRom, &code(0xC1_2345));
+        // a copier header, and a size that isn't a power of two
+        round_trip(Mode::SuperNintendo, 0x1_8200, SnesMap::LoRom, &code(0x02_8000));
+    }
+
+    #[test]
+    fn fixed_genesis_checksums_verify() {
+        let code = |address| crate::genesis::encode_genesis(&crate::genesis::Word { address, value: 0x4E71 }).unwrap();
+        round_trip(Mode::Genesis, 0x2_0000, SnesMap::Auto, &code(0x1_2344));
+        round_trip(Mode::Genesis, 0x1_0001, SnesMap::Auto, &code(0x0400));
+    }
+
+    #[test]
+    fn fixed_game_boy_checksums_verify() {
+        let code = |address| crate::gameboy::encode_gameboy(&crate::patch::Patch { address, value: vec![0x00], compare: None }, crate::tables::GAMEBOY_ROM_END).unwrap();
+        round_trip(Mode::GameBoy, 0x8000, SnesMap::Auto, &code(0x1234));
+        round_trip(Mode::GameBoy, 0x2_0000, SnesMap::Auto, &code(0x4567));
+    }
+
+    #[test]
+    fn systems_without_checksums_are_left_alone() {
+        let mut rom = vec![0xEA; 0x8010];
+        assert_eq!(fix_checksums(Mode::Nintendo, &mut rom, SnesMap::Auto).unwrap(), []);
+        assert_eq!(rom, vec![0xEA; 0x8010]);
+    }
+}
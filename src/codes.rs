@@ -0,0 +1,461 @@
+use crate::{error::CodeError, mode::Mode, nes::encode_nes, patch::Patch, tables::{fits, max_address, too_wide}, warn};
+
+pub const HEX_DIGITS: [char; 16] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F'];
+
+/// One entry of a code list with its system resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Code {
+    pub mode: Mode,
+    /// The code without its tag, uppercased with hyphens and spaces dropped
+    pub text: String,
+    /// A compare byte given with an `@XX` suffix, to apply only when the ROM already holds it
+    pub compare: Option<u8>,
+}
+
+/// Split an optional `SYSTEM:` tag off `entry` (e.g. `NES:SXIOPO`) and an
+/// optional `@XX` compare suffix (e.g. `SXIOPO@AD`), and normalise the rest,
+/// falling back to `default` when there's no tag
+pub fn parse_entry(entry: &str, default: Mode) -> Result<Code, CodeError> {
+    let (mode, code) = match entry.split_once(':') {
+        Some((tag, code)) => {
+            let tag = tag.trim();
+            let mode = tag.parse().map_err(|_| CodeError::UnknownSystem { code: entry.to_string(), tag: tag.to_string() })?;
+            (mode, code)
+        },
+        None => (default, entry),
+    };
+
+    let (code, compare) = match code.rsplit_once('@') {
+        Some((code, compare)) => (code, Some(parse_compare(entry, compare)?)),
+        None => (code, None),
+    };
+
+    Ok(Code { mode, text: normalize(code), compare })
+}
+
+/// Parse `entry` and decode it with its system's code format, applying any
+/// `@XX` compare override
+pub fn decode_entry(entry: &str, default: Mode) -> Result<(Mode, Patch), CodeError> {
+    let code = parse_entry(entry, default)?;
+    if code.text.contains('=') {
+        if code.mode != Mode::Nintendo { return Err(CodeError::RunUnsupported { code: entry.to_string(), system: code.mode }); }
+        let patch = parse_run(entry, &code.text)?;
+        return Ok((code.mode, Patch { compare: code.compare, ..patch }));
+    }
+    let Some(console) = code.mode.console() else { return Err(CodeError::Unsupported { code: entry.to_string(), system: code.mode }) };
+    // caught here so the error quotes the whole entry rather than the bare code
+    let length = code.text.chars().count();
+    if !console.expected_lengths().contains(&length) {
+        return Err(CodeError::BadLength { code: entry.to_string(), length, expected: console.expected_lengths() });
+    }
+    let mut patch = console.decode(&code.text).map_err(|err| match err {
+        // point at the character in the entry as typed, tag and hyphens included
+        CodeError::InvalidChar { index, found, expected, .. } => CodeError::InvalidChar { code: entry.to_string(), index: entry_index(entry, index), found, expected },
+        CodeError::OddAddress { address, .. } => CodeError::OddAddress { code: entry.to_string(), address },
+        CodeError::BadCheckChar { found, expected, .. } => CodeError::BadCheckChar { code: entry.to_string(), found, expected },
+        err => err,
+    })?;
+    check_address(entry, code.mode, &patch)?;
+
+    // layer the override on top, as long as it agrees with any compare the code already has
+    if let Some(requested) = code.compare {
+        match patch.compare {
+            Some(built_in) if built_in != requested => return Err(CodeError::CompareConflict { code: entry.to_string(), built_in, requested }),
+            _ => patch.compare = Some(requested),
+        }
+    }
+    Ok((code.mode, patch))
+}
+
+/// Fail when `patch`'s address is past the highest a `mode` code can reach.
+/// Every decoded patch goes through here, whichever decoder built it, so an
+/// address too wide for the system is reported as a bad code before it's
+/// mapped onto the ROM
+fn check_address(entry: &str, mode: Mode, patch: &Patch) -> Result<(), CodeError> {
+    match max_address(mode) {
+        Some(max) if patch.address > max => Err(CodeError::AddressTooWide { code: entry.to_string(), address: patch.address, system: mode }),
+        _ => Ok(()),
+    }
+}
+
+/// Parse `text`, an entry's normalised code, as an `ADDRESS=BYTES` run
+/// (`0x91D9=AD,BE,EF`): the bytes written one after another from a NES CPU
+/// address in `$8000-$FFFF`. This isn't a Game Genie code, which only ever
+/// writes one byte, but a raw patch for small hacks layered on top of cheats
+fn parse_run(entry: &str, text: &str) -> Result<Patch, CodeError> {
+    let invalid = |index: usize, found: char| CodeError::InvalidChar { code: entry.to_string(), index: entry_index(entry, index), found, expected: &HEX_DIGITS };
+    let check_hex = |field: &str, start: usize| match field.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        Some((index, found)) => Err(invalid(start + index, found)),
+        None => Ok(()),
+    };
+
+    let Some((address, bytes)) = text.split_once('=') else { unreachable!() };
+    let digits = address.strip_prefix("0X").or_else(|| address.strip_prefix('$')).unwrap_or(address);
+    let start = address.len() - digits.len();
+    check_hex(digits, start)?;
+    let address = match u32::from_str_radix(digits, 16) {
+        Ok(address) if digits.len() <= 4 => address,
+        _ => return Err(CodeError::BadLength { code: entry.to_string(), length: digits.len(), expected: &[4] }),
+    };
+
+    let mut value = Vec::new();
+    let mut start = text.len() - bytes.len();
+    for byte in bytes.split(',') {
+        check_hex(byte, start)?;
+        if let Some(value) = u32::from_str_radix(byte, 16).ok().filter(|&value| !fits(Mode::Nintendo, value)) {
+            return Err(CodeError::ValueTooWide { code: entry.to_string(), value, system: Mode::Nintendo });
+        }
+        if byte.len() != 2 { return Err(CodeError::BadLength { code: entry.to_string(), length: byte.len(), expected: &[2] }); }
+        value.push(u8::from_str_radix(byte, 16).unwrap());
+        start += byte.len() + 1;
+    }
+
+    if address < 0x8000 {
+        return Err(CodeError::RamAddress { code: entry.to_string(), address, system: Mode::Nintendo });
+    }
+    // the whole run has to fit below $10000, every byte of it in ROM
+    let last = address + value.len() as u32 - 1;
+    if last > 0xFFFF {
+        return Err(CodeError::OutOfRange { code: entry.to_string(), address: last });
+    }
+    Ok(Patch { address: address - 0x8000, value, compare: None })
+}
+
+// where the `index`th character of an entry's normalised code sits in the entry itself
+fn entry_index(entry: &str, index: usize) -> usize {
+    let code_start = entry.find(':').map_or(0, |colon| entry[..=colon].chars().count());
+    entry.chars().enumerate().skip(code_start)
+        .filter(|(_, c)| !matches!(c, '-' | ' '))
+        .nth(index)
+        .map_or(index, |(position, _)| position)
+}
+
+fn parse_compare(entry: &str, hex: &str) -> Result<u8, CodeError> {
+    if let Some((index, found)) = hex.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        let index = entry.chars().count() - hex.chars().count() + hex[..index].chars().count();
+        return Err(CodeError::InvalidChar { code: entry.to_string(), index, found, expected: &HEX_DIGITS });
+    }
+    if hex.len() != 2 {
+        return Err(CodeError::BadLength { code: entry.to_string(), length: hex.len(), expected: &[2] });
+    }
+    Ok(u8::from_str_radix(hex, 16).unwrap())
+}
+
+/// One code of a list, with the label of the cheat it came from in a code file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub code: String,
+    pub label: Option<String>,
+    /// Switched off in the code file: reported, but not applied
+    pub disabled: bool,
+    /// Guard codes from the same line, whose compare bytes must all match
+    /// the ROM for this code to be applied
+    pub guards: Vec<String>,
+}
+
+impl ListEntry {
+    pub fn new(code: &str) -> ListEntry {
+        ListEntry { code: code.to_string(), label: None, disabled: false, guards: Vec::new() }
+    }
+
+    /// A code as given in CODES, `--code` or a config file's `codes`, where
+    /// `SXIOPO?SXSOPOZS` is `SXIOPO` guarded by `SXSOPOZS`, as if they shared
+    /// a code file line
+    pub fn parse(text: &str) -> ListEntry {
+        let mut parts = text.split('?');
+        let code = parts.next().unwrap_or_default();
+        ListEntry { guards: parts.map(String::from).collect(), ..ListEntry::new(code) }
+    }
+
+    /// The entry written back in the form `parse` reads, each code grouped as
+    /// `format_code` does it and tagged if it was
+    pub fn format(&self, default: Mode) -> String {
+        let written = |code: &str| match code.split_once(':') {
+            Some((tag, _)) => format!("{}:{}", tag.trim(), format_entry(code, default)),
+            None => format_entry(code, default),
+        };
+        self.guards.iter().fold(written(&self.code), |text, guard| format!("{text}?{}", written(guard)))
+    }
+}
+
+/// Parse a code file: one cheat per line, either bare codes or
+/// `Infinite Lives: SXIOPO GXXZ` with a label, codes separated by spaces or
+/// `+`. A colon after a system name (`NES:SXIOPO`) is a tag, not a label.
+/// A line starting with `!` is switched off: its codes come back disabled
+/// rather than being dropped, so they still show in the summary. So is a line
+/// starting with `#` whose codes all decode for some system; any other `#`
+/// line is a comment. A code written `?CODE` is the line's guard: it's never
+/// applied, but the line's other codes only are when the ROM holds the
+/// guard's compare byte
+pub fn parse_code_file(src: &str) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    for line in src.lines() {
+        let trimmed = line.trim_start();
+        let (disabled, line) = match trimmed.strip_prefix(['#', '!']) {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (label, codes) = match line.split_once(':') {
+            Some((label, codes)) if label.trim().parse::<Mode>().is_err() => (Some(label.trim().to_string()), codes),
+            _ => (None, line),
+        };
+        let codes: Vec<&str> = codes.split(|c: char| c == '+' || c.is_whitespace()).filter(|code| !code.is_empty()).collect();
+        let decodes = |code: &&str| Mode::ALL.iter().any(|&mode| decode_entry(code.trim_start_matches('?'), mode).is_ok());
+        if trimmed.starts_with('#') && (codes.is_empty() || !codes.iter().all(decodes)) { continue; }
+        let guards: Vec<String> = codes.iter().filter_map(|code| code.strip_prefix('?')).map(String::from).collect();
+        for code in codes.into_iter().filter(|code| !code.starts_with('?')) {
+            entries.push(ListEntry { code: code.to_string(), label: label.clone(), disabled, guards: guards.clone() });
+        }
+    }
+    entries
+}
+
+/// Parse an FCEUX style `.cht` cheat block for the NES: one
+/// `address:value:compare:enabled` row per line in hex, with `compare` empty
+/// for none, `enabled` 0 or 1, and an optional `:name` after it. Each enabled
+/// row comes back as the Game Genie code that makes the same patch, tagged
+/// NES and labelled with its name. Disabled rows are skipped, and so are rows
+/// below `$8000`, with a warning, since those patch RAM rather than ROM
+pub fn parse_cht(src: &str) -> Result<Vec<ListEntry>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let fields: Vec<&str> = line.splitn(5, ':').map(str::trim).collect();
+        let (&[address, value, compare, enabled], name) = (&fields[..fields.len().min(4)], fields.get(4)) else {
+            return Err(format!("line {}: expected address:value:compare:enabled, found {} fields", number + 1, fields.len()));
+        };
+        let hex = |field: &str, what: &str| u32::from_str_radix(field, 16).map_err(|_| format!("line {}: bad {what} {field:?}", number + 1));
+        let byte = |field: &str, what: &str| hex(field, what).and_then(|n| u8::try_from(n).map_err(|_| format!("line {}: {}", number + 1, too_wide(Mode::Nintendo, n).replacen("value", what, 1))));
+
+        let address = hex(address, "address")?;
+        let patch = Patch {
+            address: address.wrapping_sub(0x8000),
+            value: vec![byte(value, "value")?],
+            compare: if compare.is_empty() { None } else { Some(byte(compare, "compare")?) },
+        };
+        match enabled {
+            "0" => continue,
+            "1" => {},
+            _ => return Err(format!("line {}: enabled should be 0 or 1, not {enabled:?}", number + 1)),
+        }
+        if !(0x8000..=0xFFFF).contains(&address) {
+            warn!("line {}: {address:04X} is NES RAM, not ROM; skipping it", number + 1);
+            continue;
+        }
+
+        let Some(code) = encode_nes(&patch) else { unreachable!() };
+        entries.push(ListEntry { code: format!("NES:{code}"), label: name.map(|name| name.to_string()), disabled: false, guards: Vec::new() });
+    }
+    Ok(entries)
+}
+
+/// One cheat of a cheat database: a `GameID|CheatName|Codes|Mode` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbEntry {
+    pub game: String,
+    pub name: String,
+    pub codes: Vec<String>,
+    /// The system the codes are for, or `None` when the field is empty and
+    /// they follow MODE
+    pub mode: Option<Mode>,
+}
+
+impl DbEntry {
+    /// `GameID:CheatName`, the selector that picks out just this cheat
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.game, self.name)
+    }
+
+    /// The cheat's codes, tagged with its system and labelled with its name
+    pub fn list_entries(&self) -> Vec<ListEntry> {
+        self.codes.iter().map(|code| {
+            // a code tagged in the database keeps its own system
+            let tagged = match self.mode {
+                Some(mode) if !code.contains(':') => format!("{}:{code}", mode.aliases()[0]),
+                _ => code.clone(),
+            };
+            ListEntry { code: tagged, label: Some(self.name.clone()), disabled: false, guards: Vec::new() }
+        }).collect()
+    }
+}
+
+/// Parse a cheat database, one `GameID|CheatName|Codes|Mode` cheat per line with
+/// codes separated by spaces or `+`. Blank lines and lines starting with `#`
+/// are skipped; a malformed line is an error naming its line number
+pub fn parse_db(src: &str) -> Result<Vec<DbEntry>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+
+        let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+        let [game, name, codes, mode] = fields[..] else {
+            return Err(format!("line {}: expected GameID|CheatName|Codes|Mode, found {} fields", number + 1, fields.len()));
+        };
+        let mode = match mode {
+            "" => None,
+            mode => Some(mode.parse().map_err(|err| format!("line {}: {err}", number + 1))?),
+        };
+        let codes = codes.split(|c: char| c == '+' || c.is_whitespace()).filter(|code| !code.is_empty()).map(String::from).collect();
+        entries.push(DbEntry { game: game.to_string(), name: name.to_string(), codes, mode });
+    }
+    Ok(entries)
+}
+
+/// The cheats `selector` picks out of `db`: `GameID:CheatName` for one cheat,
+/// or a bare game ID or cheat name for every cheat that has it, ignoring case
+pub fn select<'a>(db: &'a [DbEntry], selector: &str) -> Vec<&'a DbEntry> {
+    let matches = |entry: &DbEntry| {
+        entry.key().eq_ignore_ascii_case(selector) || entry.game.eq_ignore_ascii_case(selector) || entry.name.eq_ignore_ascii_case(selector)
+    };
+    db.iter().filter(|entry| matches(entry)).collect()
+}
+
+/// `raw` in its system's usual grouping: NES codes run together (`SXIOPO`),
+/// Game Boy and Game Gear codes go in threes (`00A-17B-C49`), Genesis and
+/// SNES codes in fours (`SCRA-BJX0`, `C264-64D7`, a Genesis check character
+/// trailing as `SCRA-BJX0-V`). Codes of any other length are only normalised
+pub fn format_code(mode: Mode, raw: &str) -> String {
+    let text = normalize(raw);
+    let group = match (mode, text.chars().count()) {
+        (Mode::GameBoy | Mode::GameGear, 6 | 9) => 3,
+        (Mode::Genesis, 8 | 9) | (Mode::SuperNintendo, 8) => 4,
+        _ => return text,
+    };
+    let chars: Vec<char> = text.chars().collect();
+    chars.chunks(group).map(|chunk| chunk.iter().collect::<String>()).collect::<Vec<_>>().join("-")
+}
+
+/// `entry` as `format_code` writes it, without its system tag and with any
+/// `@XX` compare suffix kept; an entry that doesn't parse comes back as is
+pub fn format_entry(entry: &str, default: Mode) -> String {
+    match parse_entry(entry, default) {
+        Ok(Code { mode, text, compare }) => {
+            let compare = compare.map_or(String::new(), |compare| format!("@{compare:02X}"));
+            format!("{}{compare}", format_code(mode, &text))
+        },
+        Err(_) => entry.to_string(),
+    }
+}
+
+/// Uppercase `code` and drop its hyphens and spaces, wherever they are, so any
+/// grouping (`ABCD-EFGH`, `AB-CDEF-GH`, `ABCDEFGH`) decodes the same
+pub fn normalize(code: &str) -> String {
+    code.chars().filter(|c| !matches!(c, '-' | ' ')).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_addresses_past_each_systems_range() {
+        let systems = [(Mode::Nintendo, 0x7FFF), (Mode::SuperNintendo, 0xFF_FFFF), (Mode::GameBoy, 0xFFFF), (Mode::GameGear, 0xFFFF), (Mode::Genesis, 0x3F_FFFE)];
+        let patch = |address| Patch { address, value: vec![0xAD], compare: None };
+        for (mode, max) in systems {
+            assert_eq!(check_address("CODE", mode, &patch(max)), Ok(()), "{mode}");
+            assert_eq!(check_address("CODE", mode, &patch(max + 2)), Err(CodeError::AddressTooWide { code: "CODE".to_string(), address: max + 2, system: mode }), "{mode}");
+        }
+        assert_eq!(check_address("CODE", Mode::MasterSystem, &patch(u32::MAX)), Ok(()));
+    }
+
+    #[test]
+    fn a_decoded_patch_moved_past_its_range_is_refused() {
+        for (entry, mode) in [("SXIOPO", Mode::Nintendo), ("C264-64D7", Mode::SuperNintendo), ("00A-17B", Mode::GameBoy), ("SCRA-BJX0", Mode::Genesis)] {
+            let (_, mut patch) = decode_entry(entry, mode).unwrap();
+            assert_eq!(check_address(entry, mode, &patch), Ok(()), "{entry}");
+            patch.address = max_address(mode).unwrap() + 1;
+            let err = check_address(entry, mode, &patch).unwrap_err();
+            assert!(matches!(err, CodeError::AddressTooWide { system, .. } if system == mode), "{entry}: {err}");
+        }
+    }
+
+    #[test]
+    fn the_highest_codable_addresses_decode() {
+        let highest = [
+            ("NES", crate::nes::encode_nes(&Patch { address: 0x7FFF, value: vec![0xAD], compare: None })),
+            ("SNES", crate::snes::encode_snes(&Patch { address: 0xFF_FFFF, value: vec![0xAD], compare: None })),
+            ("GB", crate::gameboy::encode_gameboy(&Patch { address: 0x7FFF, value: vec![0xAD], compare: None }, crate::tables::GAMEBOY_ROM_END)),
+            ("MD", crate::genesis::encode_genesis(&crate::genesis::Word { address: 0x3F_FFFE, value: 0xBEEF })),
+        ];
+        for (tag, code) in highest {
+            let entry = format!("{tag}:{}", code.unwrap());
+            assert!(decode_entry(&entry, Mode::Nintendo).is_ok(), "{entry}");
+        }
+    }
+
+    #[test]
+    fn hyphens_and_spaces_go_anywhere() {
+        let groupings = [
+            (Mode::SuperNintendo, "C264-64D7", "C2-6464-D7"),
+            (Mode::Nintendo, "SXIOPO", "S-XIO-PO"),
+            (Mode::Nintendo, "SXIOPO", "SX IO PO"),
+            (Mode::GameBoy, "00A-17B-C49", "00A17-BC49"),
+            (Mode::GameBoy, "00A-17B-C49", " 00A 17B C49 "),
+        ];
+        for (mode, canonical, grouped) in groupings {
+            assert_eq!(decode_entry(grouped, mode), decode_entry(canonical, mode), "{grouped:?}");
+            assert!(decode_entry(grouped, mode).is_ok(), "{grouped:?}");
+        }
+    }
+
+    #[test]
+    fn switches_off_hashed_and_banged_lines() {
+        let entries = parse_code_file("#SXIOPO\n!Infinite Lives: SXIOPO\n# Lives: NES:SXIOPO+AAPZ-ZZZZ\nGXXZZLVI\n");
+        let codes: Vec<(&str, bool)> = entries.iter().map(|entry| (entry.code.as_str(), entry.disabled)).collect();
+        assert_eq!(codes, [("SXIOPO", true), ("SXIOPO", true), ("NES:SXIOPO", true), ("AAPZ-ZZZZ", true), ("GXXZZLVI", false)]);
+        assert_eq!(entries[1].label.as_deref(), Some("Infinite Lives"));
+    }
+
+    #[test]
+    fn keeps_hashed_prose_as_comments() {
+        let src = "# Super Mario Bros. (USA)\n#\n# Lives: start with nine\n#TODO check these\nSXIOPO\n";
+        let entries = parse_code_file(src);
+        assert_eq!(entries, [ListEntry::new("SXIOPO")]);
+        // an unrecognised code after ! is still a code, switched off
+        assert_eq!(parse_code_file("!Lives: ZZZZZZZ").len(), 1);
+    }
+
+    #[test]
+    fn reads_guards_after_a_code() {
+        let entry = ListEntry::parse("sxiopo?NES:sxsopozs?GXXZZLVI");
+        assert_eq!((entry.code.as_str(), &entry.guards[..]), ("sxiopo", &["NES:sxsopozs".to_string(), "GXXZZLVI".to_string()][..]));
+        assert_eq!(entry.format(Mode::Nintendo), "SXIOPO?NES:SXSOPOZS?GXXZZLVI");
+        assert_eq!(ListEntry::parse("SNES:c2ab6da4").format(Mode::Nintendo), "SNES:C2AB-6DA4");
+        assert_eq!(ListEntry::parse("SXIOPO"), ListEntry::new("SXIOPO"));
+    }
+
+    #[test]
+    fn reads_code_file_guards() {
+        let entries = parse_code_file("Moon Jump: ?SXSOPOZS IIAAPA AAAAAA\n");
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.guards == ["SXSOPOZS"] && entry.label.as_deref() == Some("Moon Jump")));
+    }
+
+    #[test]
+    fn reads_cht_rows_as_nes_codes() {
+        let src = "# from FCEUX\n91D9:AD::1:Infinite lives\nD1DD:05:03:1\n9000:EA::0:Switched off\n";
+        let entries = parse_cht(src).unwrap();
+        let codes: Vec<(&str, Option<&str>)> = entries.iter().map(|entry| (entry.code.as_str(), entry.label.as_deref())).collect();
+        assert_eq!(codes, [("NES:SXIOPO", Some("Infinite lives")), ("NES:IESSIPLA", None)]);
+
+        let (mode, patch) = decode_entry(&entries[1].code, Mode::GameBoy).unwrap();
+        assert_eq!((mode, patch.address, patch.value, patch.compare), (Mode::Nintendo, 0x51DD, vec![0x05], Some(0x03)));
+    }
+
+    #[test]
+    fn skips_cht_rows_that_patch_ram() {
+        assert_eq!(parse_cht("07FF:09::1:Ninety nine lives\n").unwrap(), []);
+    }
+
+    #[test]
+    fn rejects_malformed_cht_rows() {
+        let error = |src: &str| parse_cht(src).unwrap_err();
+        assert_eq!(error("91D9:AD:1"), "line 1: expected address:value:compare:enabled, found 3 fields");
+        assert_eq!(error("\n91D9:AD::yes"), "line 2: enabled should be 0 or 1, not \"yes\"");
+        assert_eq!(error("91G9:AD::1"), "line 1: bad address \"91G9\"");
+        assert!(error("91D9:1AD::1").starts_with("line 1: "));
+    }
+}
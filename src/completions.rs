@@ -0,0 +1,172 @@
+//! Shell completion scripts, generated from the clap command so they list
+//! the same flags, subcommands and values the parser accepts.
+
+use std::io::{self, Write};
+
+use clap::{Arg, Command, ValueEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(name = "powershell")]
+    PowerShell,
+}
+
+/// Write the completion script for `shell` covering `cmd` and its subcommands
+pub fn generate(shell: Shell, cmd: &mut Command, w: &mut impl Write) -> io::Result<()> {
+    cmd.build();
+    match shell {
+        Shell::Bash => bash(cmd, w),
+        // zsh runs the bash script through its bash compatibility layer
+        Shell::Zsh => {
+            writeln!(w, "autoload -U +X bashcompinit && bashcompinit")?;
+            bash(cmd, w)
+        },
+        Shell::Fish => fish(cmd, w),
+        Shell::PowerShell => powershell(cmd, w),
+    }
+}
+
+fn flags(arg: &Arg) -> Vec<String> {
+    let long = arg.get_long().map(|long| format!("--{long}"));
+    let short = arg.get_short().map(|short| format!("-{short}"));
+    long.into_iter().chain(short).collect()
+}
+
+fn values(arg: &Arg) -> Vec<String> {
+    arg.get_possible_values().iter().filter(|value| !value.is_hide_set()).map(|value| value.get_name().to_string()).collect()
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    arg.get_num_args().is_some_and(|range| range.takes_values())
+}
+
+// numbers, sizes and names aren't worth offering file names for
+fn takes_path(arg: &Arg) -> bool {
+    arg.get_value_names().unwrap_or_default().iter().any(|name| matches!(name.as_str(), "PATH" | "DIR" | "OUTPUT"))
+}
+
+fn bash(cmd: &Command, w: &mut impl Write) -> io::Result<()> {
+    let name = cmd.get_name();
+    let subcommands: Vec<&Command> = cmd.get_subcommands().collect();
+    let names: Vec<&str> = subcommands.iter().map(|sub| sub.get_name()).collect();
+
+    writeln!(w, "_{name}() {{")?;
+    writeln!(w, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\" sub=\"\" word")?;
+    writeln!(w, "    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do")?;
+    writeln!(w, "        case \"$word\" in {}) sub=\"$word\"; break ;; esac", names.join("|"))?;
+    writeln!(w, "    done")?;
+    writeln!(w, "    case \"$sub\" in")?;
+    for sub in subcommands.iter().copied().chain([cmd]) {
+        let pattern = if sub.get_name() == name { "*".to_string() } else { sub.get_name().to_string() };
+        writeln!(w, "        {pattern})")?;
+
+        // a flag's own values first, then the flags, subcommands and positional values
+        let options: Vec<&Arg> = sub.get_arguments().filter(|arg| !arg.is_positional() && takes_value(arg)).collect();
+        if !options.is_empty() {
+            writeln!(w, "            case \"$prev\" in")?;
+            for arg in options {
+                let completion = match values(arg) {
+                    _ if takes_path(arg) => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+                    values if values.is_empty() => "COMPREPLY=()".to_string(),
+                    values => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", values.join(" ")),
+                };
+                writeln!(w, "                {}) {completion}; return ;;", flags(arg).join("|"))?;
+            }
+            writeln!(w, "            esac")?;
+        }
+
+        let mut words: Vec<String> = sub.get_arguments().flat_map(flags).collect();
+        words.extend(sub.get_arguments().filter(|arg| arg.is_positional()).flat_map(values));
+        if sub.get_name() == name { words.extend(names.iter().map(|name| name.to_string())); }
+        writeln!(w, "            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\") $(compgen -f -- \"$cur\"))", words.join(" "))?;
+        writeln!(w, "            ;;")?;
+    }
+    writeln!(w, "    esac")?;
+    writeln!(w, "}}")?;
+    writeln!(w, "complete -F _{name} {name}")
+}
+
+// single quotes for fish, with backslashes and quotes inside them escaped
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(cmd: &Command, w: &mut impl Write) -> io::Result<()> {
+    let name = cmd.get_name();
+    let names: Vec<&str> = cmd.get_subcommands().map(|sub| sub.get_name()).collect();
+
+    for sub in cmd.get_subcommands() {
+        let about = sub.get_about().map(|about| about.to_string()).unwrap_or_default();
+        writeln!(w, "complete -c {name} -n __fish_use_subcommand -f -a {} -d {}", sub.get_name(), fish_quote(&about))?;
+    }
+    for sub in cmd.get_subcommands().chain([cmd]) {
+        let condition = match sub.get_name() == name {
+            true => format!("not __fish_seen_subcommand_from {}", names.join(" ")),
+            false => format!("__fish_seen_subcommand_from {}", sub.get_name()),
+        };
+        for arg in sub.get_arguments() {
+            let help = arg.get_help().map(|help| help.to_string()).unwrap_or_default();
+            let values = values(arg);
+            let mut line = format!("complete -c {name} -n {}", fish_quote(&condition));
+            if arg.is_positional() {
+                if values.is_empty() { continue; }
+            } else {
+                if let Some(long) = arg.get_long() { line += &format!(" -l {long}"); }
+                if let Some(short) = arg.get_short() { line += &format!(" -s {short}"); }
+                if takes_value(arg) { line += if takes_path(arg) || !values.is_empty() { " -r" } else { " -r -f" }; }
+            }
+            if !values.is_empty() { line += &format!(" -f -a {}", fish_quote(&values.join(" "))); }
+            writeln!(w, "{line} -d {}", fish_quote(&help))?;
+        }
+    }
+    Ok(())
+}
+
+// single quotes for PowerShell, which escapes a quote inside them by doubling it
+fn ps_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn ps_list(words: &[String]) -> String {
+    format!("@({})", words.iter().map(|word| ps_quote(word)).collect::<Vec<_>>().join(", "))
+}
+
+fn powershell(cmd: &Command, w: &mut impl Write) -> io::Result<()> {
+    let name = cmd.get_name();
+    let names: Vec<String> = cmd.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+
+    writeln!(w, "Register-ArgumentCompleter -Native -CommandName {name} -ScriptBlock {{")?;
+    writeln!(w, "    param($wordToComplete, $commandAst, $cursorPosition)")?;
+    writeln!(w, "    $words = @($commandAst.CommandElements | ForEach-Object {{ $_.Extent.Text }})")?;
+    writeln!(w, "    $prev = if ($wordToComplete) {{ $words[-2] }} else {{ $words[-1] }}")?;
+    writeln!(w, "    $sub = $words | Select-Object -Skip 1 | Where-Object {{ {} -contains $_ }} | Select-Object -First 1", ps_list(&names))?;
+    writeln!(w, "    $values = @{{}}")?;
+    writeln!(w, "    switch ($sub) {{")?;
+    for sub in cmd.get_subcommands().chain([cmd]) {
+        let pattern = if sub.get_name() == name { "default".to_string() } else { ps_quote(sub.get_name()) };
+        writeln!(w, "        {pattern} {{")?;
+
+        // a flag's own values, by flag; none (so PowerShell offers paths) for the rest
+        for arg in sub.get_arguments().filter(|arg| !arg.is_positional() && takes_value(arg)) {
+            let values = if takes_path(arg) { Vec::new() } else { values(arg) };
+            for flag in flags(arg) {
+                writeln!(w, "            $values[{}] = {}", ps_quote(&flag), ps_list(&values))?;
+            }
+        }
+
+        let mut words: Vec<String> = sub.get_arguments().flat_map(flags).collect();
+        words.extend(sub.get_arguments().filter(|arg| arg.is_positional()).flat_map(values));
+        if sub.get_name() == name { words.extend(names.iter().cloned()); }
+        writeln!(w, "            $candidates = {}", ps_list(&words))?;
+        writeln!(w, "        }}")?;
+    }
+    writeln!(w, "    }}")?;
+    writeln!(w, "    if ($prev -and $values.ContainsKey($prev)) {{ $candidates = $values[$prev] }}")?;
+    writeln!(w, "    $candidates | Where-Object {{ $_ -like \"$wordToComplete*\" }} | ForEach-Object {{")?;
+    writeln!(w, "        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)")?;
+    writeln!(w, "    }}")?;
+    writeln!(w, "}}")
+}
@@ -0,0 +1,203 @@
+//! Just enough TOML for job files: `key = value` pairs of strings,
+//! integers, booleans and (multi-line) arrays, with `#` comments. Tables
+//! aren't supported.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String,
+}
+
+pub type Table = HashMap<String, Value>;
+
+pub fn parse(src: &str) -> Result<Table, ConfigError> {
+    let mut table = Table::new();
+    let mut parser = Parser { chars: src.chars().collect(), pos: 0, line: 1 };
+
+    loop {
+        parser.skip_blank_lines();
+        if parser.peek().is_none() { break; }
+
+        let line = parser.line;
+        if parser.peek() == Some('[') { return Err(parser.error("tables aren't supported")); }
+        let key = parser.key()?;
+        parser.skip_spaces();
+        if parser.next() != Some('=') { return Err(parser.error(&format!("expected '=' after {key}"))); }
+        parser.skip_spaces();
+        let value = parser.value()?;
+        parser.end_of_line()?;
+
+        if table.insert(key.clone(), value).is_some() {
+            return Err(ConfigError { line, message: format!("{key} is set more than once") });
+        }
+    }
+
+    Ok(table)
+}
+
+/// `s` as a double quoted string `parse` reads back as `s`
+pub fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> ConfigError {
+        ConfigError { line: self.line, message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' { self.line += 1; }
+        Some(c)
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) { self.next(); }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) { self.next(); }
+        }
+    }
+
+    // whitespace, newlines and comments, as allowed between entries and inside arrays
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => { self.next(); },
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), ConfigError> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n' | '\r') => Ok(()),
+            Some(c) => Err(self.error(&format!("unexpected '{c}' after value"))),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, ConfigError> {
+        if matches!(self.peek(), Some('"' | '\'')) { return self.string(); }
+
+        let mut key = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-')) {
+            key.push(c);
+            self.next();
+        }
+        if key.is_empty() { return Err(self.error("expected a key")); }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, ConfigError> {
+        match self.peek() {
+            Some('"' | '\'') => self.string().map(Value::String),
+            Some('[') => self.array(),
+            Some('t' | 'f') => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => Err(self.error(&format!("unexpected '{word}'"))),
+                }
+            },
+            Some(c) if c.is_ascii_digit() || matches!(c, '-' | '+') => {
+                let word = self.word().replace('_', "");
+                let parsed = match word.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => word.parse(),
+                };
+                parsed.map(Value::Integer).map_err(|_| self.error(&format!("invalid integer '{word}'")))
+            },
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+')) {
+            word.push(c);
+            self.next();
+        }
+        word
+    }
+
+    fn string(&mut self) -> Result<String, ConfigError> {
+        let quote = self.next();
+        let mut s = String::new();
+
+        loop {
+            match self.next() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(c) if Some(c) == quote => return Ok(s),
+                // literal 'strings' take backslashes as-is
+                Some('\\') if quote == Some('"') => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some(c) => return Err(self.error(&format!("unknown escape '\\{c}'"))),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ConfigError> {
+        self.next();
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(']') { self.next(); return Ok(Value::Array(items)); }
+
+            items.push(self.value()?);
+            self.skip_blank_lines();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+}
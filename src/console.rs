@@ -0,0 +1,16 @@
+use crate::{error::CodeError, patch::Patch};
+
+/// A system's Game Genie code format
+pub trait Console {
+    /// Decode one normalised code (no tag, hyphens or lowercase)
+    fn decode(&self, code: &str) -> Result<Patch, CodeError>;
+
+    /// Every length, in characters without hyphens, a code can have
+    fn expected_lengths(&self) -> &'static [usize];
+
+    /// The characters codes are written with, in value order
+    fn alphabet(&self) -> &'static [char];
+
+    /// How codes are written: length, alphabet and whether they're checked
+    fn format(&self) -> String;
+}
@@ -0,0 +1,166 @@
+use std::{error, fmt, io};
+
+use crate::{codes::HEX_DIGITS, mode::Mode, tables::{max_address, too_wide}};
+
+/// Exit statuses, one per failure category; `Error::exit_code` picks between them
+pub const EXIT_SUCCESS: i32 = 0;
+/// Bad arguments, or a subcommand the system doesn't support (clap exits with this too)
+pub const EXIT_USAGE: i32 = 2;
+/// A code that doesn't decode
+pub const EXIT_INVALID_CODE: i32 = 3;
+/// INPUT or OUTPUT couldn't be read or written, or isn't a usable ROM
+pub const EXIT_ROM: i32 = 4;
+/// A code decoded but wasn't applied: its address isn't ROM, or its compare byte didn't match
+pub const EXIT_NOT_APPLIED: i32 = 5;
+/// Everything applied, but `--strict` was given and there were warnings
+pub const EXIT_WARNINGS: i32 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeError {
+    /// A character that isn't part of the system's code alphabet, `expected`,
+    /// at `index` (counting characters of `code`, from 0)
+    InvalidChar { code: String, index: usize, found: char, expected: &'static [char] },
+    /// A code that isn't one of the lengths the system accepts
+    BadLength { code: String, length: usize, expected: &'static [usize] },
+    /// A `SYSTEM:` tag that doesn't name a known mode
+    UnknownSystem { code: String, tag: String },
+    /// A code whose address isn't backed by ROM in this file
+    OutOfRange { code: String, address: u32 },
+    /// A code that decoded to an address past the highest its system's codes reach,
+    /// which usually means it's for another system
+    AddressTooWide { code: String, address: u32, system: Mode },
+    /// A code that writes to RAM, which only exists on a running system, not in the ROM file
+    RamAddress { code: String, address: u32, system: Mode },
+    /// A Genesis code whose word would land on an odd address, which the 68000 can't write
+    OddAddress { code: String, address: u32 },
+    /// A code whose check character doesn't match the rest of it
+    BadCheckChar { code: String, found: char, expected: char },
+    /// A code for a system rggp can't decode yet
+    Unsupported { code: String, system: Mode },
+    /// An `ADDRESS=BYTES` run for a system other than the NES
+    RunUnsupported { code: String, system: Mode },
+    /// A value in a raw `ADDRESS=BYTES` run wider than the system writes
+    ValueTooWide { code: String, value: u32, system: Mode },
+    /// A guard code with no compare byte to check the ROM against
+    GuardWithoutCompare { code: String },
+    /// An `@XX` compare override on a code that already has a different compare byte
+    CompareConflict { code: String, built_in: u8, requested: u8 },
+}
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodeError::InvalidChar { code, index, found, expected } => {
+                write!(f, "Invalid character {found:?} at position {} of {code}", index + 1)?;
+                let alphabet: String = expected.iter().collect();
+                // the NES and SNES reorder (or replace) hex digits, which trips people up
+                if expected.len() == 16 && **expected != HEX_DIGITS {
+                    write!(f, " (its digits are {alphabet} for 0-F, not standard hex)")
+                } else {
+                    write!(f, " (expected one of {alphabet})")
+                }
+            },
+            CodeError::BadLength { code, length, expected } => {
+                let expected: Vec<String> = expected.iter().map(|n| n.to_string()).collect();
+                if *length == 0 {
+                    write!(f, "Empty code {code:?}, expected {} characters", expected.join(" or "))
+                } else {
+                    write!(f, "Invalid code length for {code} ({length}), expected {}", expected.join(" or "))
+                }
+            },
+            CodeError::UnknownSystem { code, tag } => write!(f, "Unknown system {tag} in {code}"),
+            CodeError::OutOfRange { code, address } => write!(f, "{code} targets {address:06X}, which isn't mapped to ROM"),
+            CodeError::AddressTooWide { code, address, system } => {
+                let max = max_address(*system).unwrap_or_default();
+                write!(f, "{code} decodes to {address:06X}, past {max:06X}, the highest address {system} codes reach; is MODE right?")
+            },
+            CodeError::RamAddress { code, address, system } => {
+                write!(f, "{code} writes {system} RAM at {address:04X}; RAM codes only work on hardware or in an emulator, not patched into a ROM file")
+            },
+            CodeError::OddAddress { code, address } => {
+                write!(f, "{code} writes a word at odd address {address:06X}; Genesis words have to be at even addresses, so the code is bad")
+            },
+            CodeError::BadCheckChar { code, found, expected } => {
+                write!(f, "{code} ends in check character {found:?} but its other characters give {expected:?}; one of them is mistyped")
+            },
+            CodeError::Unsupported { code, system } => write!(f, "Can't decode {code}: {system} codes aren't supported yet"),
+            CodeError::RunUnsupported { code, system } => write!(f, "Can't apply {code}: ADDRESS=BYTES runs are a NES extension, not available for {system}"),
+            CodeError::ValueTooWide { code, value, system } => write!(f, "{code}: {}", too_wide(*system, *value)),
+            CodeError::GuardWithoutCompare { code } => {
+                write!(f, "Guard ?{code} has no compare byte to check; guards need an 8 letter NES or 9 digit Game Boy code, or an @XX suffix")
+            },
+            CodeError::CompareConflict { code, built_in, requested } => {
+                write!(f, "{code} already compares against {built_in:02X}, it can't be overridden with @{requested:02X}")
+            },
+        }
+    }
+}
+
+impl error::Error for CodeError {}
+
+/// Anything that can stop a patch run: a bad code, or the ROM file itself
+#[derive(Debug)]
+pub enum Error {
+    Code(CodeError),
+    Io(io::Error),
+    /// INPUT reads fine but isn't a ROM we should patch (too small, bad checksum)
+    Rom(String),
+    /// This many codes were skipped because the ROM didn't hold their compare byte
+    CompareMismatch(usize),
+    /// This many warnings were logged and `--strict` makes them fatal
+    Warnings(usize),
+    /// Some files of a directory INPUT failed; the run exits as the first of them did
+    Batch { failed: usize, total: usize, first: Box<Error> },
+    /// Options that can't work together, found only once the run was under way
+    Usage(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Code(CodeError::OutOfRange { .. } | CodeError::RamAddress { .. }) | Error::CompareMismatch(_) => EXIT_NOT_APPLIED,
+            Error::Code(_) => EXIT_INVALID_CODE,
+            Error::Io(_) | Error::Rom(_) => EXIT_ROM,
+            Error::Warnings(_) => EXIT_WARNINGS,
+            Error::Batch { first, .. } => first.exit_code(),
+            Error::Usage(_) => EXIT_USAGE,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Code(err) => err.fmt(f),
+            Error::Io(err) => err.fmt(f),
+            Error::Rom(message) | Error::Usage(message) => f.write_str(message),
+            Error::CompareMismatch(1) => f.write_str("1 code was skipped because its compare byte didn't match; --force writes it anyway"),
+            Error::CompareMismatch(n) => write!(f, "{n} codes were skipped because their compare bytes didn't match; --force writes them anyway"),
+            Error::Warnings(n) => write!(f, "{n} warning{} with --strict; nothing was written", if *n == 1 { "" } else { "s" }),
+            Error::Batch { failed, total, .. } => write!(f, "{failed} of {total} files failed"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Code(err) => Some(err),
+            Error::Io(err) => Some(err),
+            Error::Batch { first, .. } => Some(first.as_ref()),
+            Error::Rom(_) | Error::CompareMismatch(_) | Error::Warnings(_) | Error::Usage(_) => None,
+        }
+    }
+}
+
+impl From<CodeError> for Error {
+    fn from(err: CodeError) -> Error {
+        Error::Code(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
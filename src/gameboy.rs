@@ -0,0 +1,292 @@
+use std::io;
+
+use crate::{codes::{normalize, HEX_DIGITS}, console::Console, debug, error::CodeError, mode::Mode, patch::Patch, rom::{Image, Mapping}};
+
+/// The memory bank controller a Game Boy (or Game Boy Color) cartridge
+/// declares at 0x147, which decides what `$4000-$7FFF` can switch to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mbc {
+    /// 32KB of ROM, with nothing to switch
+    None,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+    /// Any other controller, taken to reach every bank
+    Other(u8),
+}
+
+impl Mbc {
+    /// The controller for cartridge type byte `kind`
+    pub fn from_type(kind: u8) -> Mbc {
+        match kind {
+            0x00 | 0x08 | 0x09 => Mbc::None,
+            0x01..=0x03 => Mbc::Mbc1,
+            0x05 | 0x06 => Mbc::Mbc2,
+            0x0F..=0x13 => Mbc::Mbc3,
+            0x19..=0x1E => Mbc::Mbc5,
+            kind => Mbc::Other(kind),
+        }
+    }
+
+    /// Whether the controller can map 16KB ROM bank `bank` into `$4000-$7FFF`.
+    /// MBC1 reads a bank number with its low 5 bits clear as one more, so
+    /// banks `$20`, `$40` and `$60` are only ever seen at `$0000-$3FFF`
+    pub fn can_switch_to(self, bank: u64) -> bool {
+        match self {
+            Mbc::None => bank == 1,
+            Mbc::Mbc1 => !bank.is_multiple_of(0x20),
+            _ => bank != 0,
+        }
+    }
+}
+
+/// Where CPU addresses land in a Game Boy or Game Gear file, which has no
+/// header in front of the ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// File length; codes past it aren't backed by ROM
+    pub size: u64,
+    /// First CPU address past the cartridge ROM window
+    pub rom_end: u32,
+    /// The Game Boy cartridge's controller, `Mbc::None` for the Game Gear
+    pub mbc: Mbc,
+    /// 16KB ROM bank to resolve `$4000-$7FFF` into, instead of every bank
+    /// the controller can switch there
+    pub bank: Option<u64>,
+}
+
+impl Layout {
+    /// Game Boy ROM is mapped at `$0000-$7FFF`
+    pub const GAMEBOY_ROM_END: u32 = 0x8000;
+    /// The Game Gear's three mapper slots cover `$0000-$BFFF`
+    pub const GAMEGEAR_ROM_END: u32 = 0xC000;
+
+    pub fn detect<I: Image + ?Sized>(image: &I, rom_end: u32) -> io::Result<Layout> {
+        let size = image.size()?;
+        debug!("{size:#X} bytes of ROM, CPU window ends at {rom_end:#X}");
+
+        // a Game Gear's Sega mapper isn't described in its header, so only Game Boy carts are banked
+        let mut mbc = Mbc::None;
+        if rom_end == Layout::GAMEBOY_ROM_END && size >= 0x150 {
+            let mut kind = [0_u8; 1];
+            image.read_bytes(&mut kind, 0x147)?;
+            mbc = Mbc::from_type(kind[0]);
+            debug!("Cartridge type {:#04X}: {mbc:?}", kind[0]);
+        }
+        Ok(Layout { size, rom_end, mbc, bank: None })
+    }
+
+    /// Every file offset CPU `address` can reach. On a cartridge with a
+    /// controller, `$4000-$7FFF` holds whichever bank the game switches in,
+    /// so without a `bank` that's the same spot in each bank it can switch
+    /// to, as a Game Genie on the real console would patch whichever one is there
+    pub fn file_offsets(&self, address: u32) -> Vec<u64> {
+        match self.switched(address) {
+            true if self.bank.is_none() && self.mbc != Mbc::None && self.size > 0x8000 => {
+                (1..self.size.div_ceil(0x4000)).filter(|&bank| self.mbc.can_switch_to(bank)).filter_map(|bank| self.in_bank(bank, address)).collect()
+            },
+            _ => self.file_offset(address).into_iter().collect(),
+        }
+    }
+
+    /// File offset of CPU `address`, as mapped with the power-on banks (or
+    /// `bank`, for the switchable window), or `None` when it isn't ROM
+    pub fn file_offset(&self, address: u32) -> Option<u64> {
+        match self.bank {
+            Some(bank) if self.switched(address) => self.in_bank(bank, address),
+            _ => (address < self.rom_end && (address as u64) < self.size).then_some(address as u64),
+        }
+    }
+
+    // whether `address` is in the Game Boy's switchable `$4000-$7FFF` window
+    fn switched(&self, address: u32) -> bool {
+        self.rom_end == Layout::GAMEBOY_ROM_END && bank(address).is_none() && address < self.rom_end
+    }
+
+    // file offset of switchable window `address` with ROM bank `bank` mapped there
+    fn in_bank(&self, bank: u64, address: u32) -> Option<u64> {
+        let offset = bank * 0x4000 + (address as u64 & 0x3FFF);
+        (offset < self.size).then_some(offset)
+    }
+}
+
+impl Mapping for Layout {
+    fn file_offset(&self, address: u32) -> Option<u64> {
+        Layout::file_offset(self, address)
+    }
+
+    fn file_offsets(&self, address: u32) -> Vec<u64> {
+        Layout::file_offsets(self, address)
+    }
+
+    /// A Game Boy cartridge's interrupt vectors, entry point and header fill
+    /// the first 0x150 bytes; the Game Gear's header is at the end of its
+    /// first 32KB instead, with code in front of it
+    fn header_len(&self) -> u64 {
+        if self.rom_end == Layout::GAMEBOY_ROM_END { 0x150 } else { 0 }
+    }
+
+    fn explain(&self, address: u32) -> Vec<String> {
+        let mut steps = vec![format!("CPU address {address:04X}, ROM window {:04X}-{:04X}", 0, self.rom_end - 1)];
+        let offsets = self.file_offsets(address);
+        let chosen = self.bank.filter(|_| self.switched(address));
+        steps.push(match (address < self.rom_end, bank(address), chosen) {
+            (false, ..) => format!("{address:04X} is past the ROM window, in RAM"),
+            (true, Some(bank), _) => format!("fixed bank {bank}, no header in front: file offset {address:X}"),
+            (true, None, Some(bank)) => {
+                format!("--bank {bank}: {bank} x 4000 + ({address:04X} & 3FFF) = file offset {:X}", bank * 0x4000 + (address as u64 & 0x3FFF))
+            },
+            (true, None, None) if offsets.len() > 1 => {
+                format!("switchable bank; {:?} can switch in {} of the {:X} byte file's 16KB banks, so every one gets N x 4000 + ({address:04X} & 3FFF)", self.mbc, offsets.len(), self.size)
+            },
+            (true, None, None) => format!("switchable bank, taken as the one mapped at power on, no header in front: file offset {address:X}"),
+        });
+        match offsets[..] {
+            [] if address < self.rom_end => steps.push(format!("the file is only {:X} bytes, so it isn't ROM", self.size)),
+            [_, _, ..] => steps.push(format!("file offsets {}", offsets.iter().map(|offset| format!("{offset:X}")).collect::<Vec<_>>().join(", "))),
+            _ => {},
+        }
+        steps
+    }
+}
+
+/// The ROM bank a Game Boy address is fixed to, or `None` for the
+/// switchable `$4000-$7FFF` window, where the cartridge mapper decides
+pub fn bank(address: u32) -> Option<u32> {
+    (address < 0x4000).then_some(0)
+}
+
+/// 6 hex digits, or 9 with a compare byte
+pub const GAMEBOY_LENGTHS: &[usize] = &[6, 9];
+
+/// Where the ROM window ends for the modes that use the Game Boy code format
+pub fn rom_end(mode: Mode) -> Option<u32> {
+    match mode {
+        Mode::GameBoy => Some(Layout::GAMEBOY_ROM_END),
+        Mode::GameGear => Some(Layout::GAMEGEAR_ROM_END),
+        _ => None,
+    }
+}
+
+/// Whether CPU `address` is past the ROM window ending at `rom_end`, in
+/// video, cartridge or work RAM, which a code can only patch while the game runs
+pub fn is_ram(address: u32, rom_end: u32) -> bool {
+    (rom_end..=0xFFFF).contains(&address)
+}
+
+/// Game Boy and Game Gear Game Genies share one code format
+pub struct GameBoy;
+
+impl Console for GameBoy {
+    fn decode(&self, code: &str) -> Result<Patch, CodeError> {
+        parse_gameboy(code)
+    }
+
+    fn expected_lengths(&self) -> &'static [usize] {
+        GAMEBOY_LENGTHS
+    }
+
+    fn alphabet(&self) -> &'static [char] {
+        &HEX_DIGITS
+    }
+
+    fn format(&self) -> String {
+        "XXX-XXX or XXX-XXX-XXX, hex digits; 9 digit codes carry a compare byte".to_string()
+    }
+}
+
+pub fn parse_gameboy(code: &str) -> Result<Patch, CodeError> {
+    let digits: Vec<char> = normalize(code).chars().collect();
+    if !GAMEBOY_LENGTHS.contains(&digits.len()) {
+        return Err(CodeError::BadLength { code: code.to_string(), length: digits.len(), expected: GAMEBOY_LENGTHS });
+    }
+
+    let mut data_hex = Vec::with_capacity(digits.len());
+    for (index, &i) in digits.iter().enumerate() {
+        match i.to_digit(16) {
+            Some(x) => data_hex.push(x),
+            None => return Err(CodeError::InvalidChar { code: code.to_string(), index, found: i, expected: &HEX_DIGITS }),
+        }
+    }
+
+    // ABC-DEF-GHI: AB is the value, FCDE the address with its top nibble inverted
+    let value = ((data_hex[0] << 4) | data_hex[1]) as u8;
+    let address = ((data_hex[5] ^ 0xF) << 12) | (data_hex[2] << 8) | (data_hex[3] << 4) | data_hex[4];
+
+    // GI is the compare byte xored with BA and rotated left 2; H is unused
+    let compare = (digits.len() == 9).then(|| (((data_hex[6] << 4) | data_hex[8]) as u8).rotate_right(2) ^ 0xBA);
+
+    Ok(Patch { address, value: vec![value], compare })
+}
+
+/// Build the `ABC-DEF` (or `ABC-DEF-GHI`, with a compare byte) code for
+/// `patch`, or `None` when its address is at or past `rom_end` (one of the
+/// `Layout` constants) or the value isn't a single byte
+pub fn encode_gameboy(patch: &Patch, rom_end: u32) -> Option<String> {
+    let [value] = patch.value[..] else { return None };
+    if patch.address >= rom_end { return None; }
+
+    // the inverted top nibble comes out 8-F for Game Boy ROM addresses
+    let address = patch.address;
+    let mut digits = vec![value >> 4, value & 0xF, (address >> 8) as u8 & 0xF, (address >> 4) as u8 & 0xF, address as u8 & 0xF, (address >> 12) as u8 ^ 0xF];
+    if let Some(compare) = patch.compare {
+        let scrambled = (compare ^ 0xBA).rotate_left(2);
+        // H doesn't affect the patch, but real codes always have it as G with the top bit flipped
+        digits.extend([scrambled >> 4, (scrambled >> 4) ^ 0x8, scrambled & 0xF]);
+    }
+
+    let hex: Vec<String> = digits.chunks(3).map(|group| group.iter().map(|digit| format!("{digit:X}")).collect()).collect();
+    Some(hex.join("-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    #[test]
+    fn decodes_six_and_nine_digit_codes() {
+        // 00A-17B-C49: write 00 at $4A17 where the ROM holds C8
+        assert_eq!(parse_gameboy("00A-17B").unwrap(), Patch { address: 0x4A17, value: vec![0x00], compare: None });
+        assert_eq!(parse_gameboy("00A-17B-C49").unwrap(), Patch { address: 0x4A17, value: vec![0x00], compare: Some(0xC8) });
+    }
+
+    #[test]
+    fn splits_fixed_and_switchable_banks() {
+        assert_eq!(bank(0x0150), Some(0));
+        assert_eq!(bank(0x3FFF), Some(0));
+        assert_eq!(bank(0x4A17), None);
+    }
+
+    #[test]
+    fn resolves_the_switchable_window_into_every_mbc1_bank() {
+        // a 2MB MBC1 cartridge: 128 banks, so $20, $40 and $60 are among them
+        let mut rom = vec![0_u8; 0x80 * 0x4000];
+        rom[0x147] = 0x01;
+        let layout = Layout::detect(&rom[..], Layout::GAMEBOY_ROM_END).unwrap();
+        assert_eq!(layout.mbc, Mbc::Mbc1);
+
+        let banks: Vec<u64> = (1..0x80).filter(|bank| ![0x20, 0x40, 0x60].contains(bank)).collect();
+        let offsets: Vec<u64> = banks.iter().map(|bank| bank * 0x4000 + 0x0A17).collect();
+        assert_eq!(layout.file_offsets(0x4A17), offsets);
+        // bank 0 is fixed, so the low half has one offset
+        assert_eq!(layout.file_offsets(0x0A17), [0x0A17]);
+        // --bank picks one outright
+        assert_eq!(Layout { bank: Some(5), ..layout }.file_offsets(0x4A17), [5 * 0x4000 + 0x0A17]);
+    }
+
+    #[test]
+    fn random_patches_round_trip() {
+        let mut rng = Rng::new(0x4742);
+        for rom_end in [Layout::GAMEBOY_ROM_END, Layout::GAMEGEAR_ROM_END] {
+            for _ in 0..2000 {
+                let patch = Patch { address: rng.below(rom_end as u64) as u32, value: vec![rng.byte()], compare: rng.maybe_byte() };
+                let code = encode_gameboy(&patch, rom_end).unwrap();
+                assert_eq!(parse_gameboy(&code).unwrap(), patch, "{code}");
+            }
+            // nothing past the ROM window is encoded
+            assert_eq!(encode_gameboy(&Patch { address: rom_end, value: vec![0], compare: None }, rom_end), None);
+        }
+    }
+}
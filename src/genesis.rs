@@ -0,0 +1,210 @@
+use std::io;
+
+use crate::{codes::normalize, console::Console, debug, error::CodeError, patch::Patch, rom::{Image, Mapping}};
+
+pub const GENESIS_CONVERSION: [char; 32] = [
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K', 'L', 'M', 'N', 'P', 'R', 'S',
+    'T', 'V', 'W', 'X', 'Y', 'Z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// The 4th characters that keep a code's address inside the 4MB of cartridge ROM
+pub const GENESIS_FOURTH: [char; 8] = ['A', 'B', 'C', 'D', 'T', 'V', 'W', 'X'];
+
+/// 8 characters, or 9 with a trailing check character
+pub const GENESIS_LENGTHS: &[usize] = &[8, 9];
+
+/// A decoded Genesis code: write the 16-bit `value` at the 24-bit `address`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Word {
+    pub address: u32,
+    pub value: u16,
+}
+
+/// A Genesis cartridge's ROM sits at address 0 with no header in front, so
+/// addresses are file offsets as long as they're inside the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u64,
+}
+
+impl Layout {
+    pub fn detect<I: Image + ?Sized>(image: &I) -> io::Result<Layout> {
+        let size = image.size()?;
+        debug!("{size:#X} bytes of Genesis ROM");
+        Ok(Layout { size })
+    }
+
+    pub fn file_offset(&self, address: u32) -> Option<u64> {
+        // the whole word has to fit
+        ((address as u64) + 2 <= self.size).then_some(address as u64)
+    }
+}
+
+impl Mapping for Layout {
+    fn file_offset(&self, address: u32) -> Option<u64> {
+        Layout::file_offset(self, address)
+    }
+
+    /// The 68000 vectors and the cartridge header fill the first 0x200 bytes
+    fn header_len(&self) -> u64 {
+        0x200
+    }
+
+    fn explain(&self, address: u32) -> Vec<String> {
+        match self.file_offset(address) {
+            Some(offset) => vec![format!("address {address:06X}: ROM starts at address 0 with nothing in front, so file offset {offset:X}")],
+            None => vec![format!("address {address:06X}: the word there is past the end of the {:X} byte file", self.size)],
+        }
+    }
+}
+
+pub struct Genesis;
+
+impl Console for Genesis {
+    fn decode(&self, code: &str) -> Result<Patch, CodeError> {
+        parse_genesis(code).map(|word| Patch { address: word.address, value: word.value.to_be_bytes().to_vec(), compare: None })
+    }
+
+    fn expected_lengths(&self) -> &'static [usize] {
+        GENESIS_LENGTHS
+    }
+
+    fn alphabet(&self) -> &'static [char] {
+        &GENESIS_CONVERSION
+    }
+
+    fn format(&self) -> String {
+        format!("XXXX-XXXX (or XXXX-XXXX-X with a check character), 8 of {}; writes a 16-bit word, never checked", GENESIS_CONVERSION.iter().collect::<String>())
+    }
+}
+
+/// The 9th check character: the sum of the 8 code characters' values, mod
+/// 32, as a code character. The Game Genie itself only takes 8 characters
+/// and no published format for a 9th has turned up, so this is rggp's own
+/// checksum rather than a documented one; a list that computes its extra
+/// character some other way will have its codes rejected
+pub fn check_char(digits: &[char]) -> Option<char> {
+    let sum = digits.iter().map(|&c| GENESIS_CONVERSION.iter().position(|&x| x == c)).sum::<Option<usize>>()?;
+    Some(GENESIS_CONVERSION[sum % 32])
+}
+
+pub fn parse_genesis(code: &str) -> Result<Word, CodeError> {
+    let mut digits: Vec<char> = normalize(code).chars().collect();
+    if !GENESIS_LENGTHS.contains(&digits.len()) {
+        return Err(CodeError::BadLength { code: code.to_string(), length: digits.len(), expected: GENESIS_LENGTHS });
+    }
+
+    let check = (digits.len() == 9).then(|| digits.pop()).flatten();
+
+    let mut data: u64 = 0;
+    for (index, &i) in digits.iter().enumerate() {
+        match GENESIS_CONVERSION.iter().position(|&c| c == i) {
+            Some(x) => data = (data << 5) | x as u64,
+            None => return Err(CodeError::InvalidChar { code: code.to_string(), index, found: i, expected: &GENESIS_CONVERSION }),
+        }
+    }
+
+    // only checked once the 8 code characters are known to be valid
+    if let Some(found) = check {
+        if !GENESIS_CONVERSION.contains(&found) {
+            return Err(CodeError::InvalidChar { code: code.to_string(), index: 8, found, expected: &GENESIS_CONVERSION });
+        }
+        let expected = check_char(&digits).unwrap_or('?');
+        if found != expected {
+            return Err(CodeError::BadCheckChar { code: code.to_string(), found, expected });
+        }
+    }
+
+    // the 4th char carries the top address bits, which must stay clear for codes to land in cartridge ROM
+    if (data >> 20) & 0b01100 != 0 {
+        return Err(CodeError::InvalidChar { code: code.to_string(), index: 3, found: digits[3], expected: &GENESIS_FOURTH });
+    }
+
+    // 40 bits, 5 per char, shuffled from a 24-bit address ABCDEFGH IJKLMNOP QRSTUVWX and 16-bit value abcdefgh ijklmnop
+    /*
+        ijklm nopIJ KLMNO PABCD EFGHd efgha bcQRS TUVWX
+     */
+    let address = (((data >> 16) & 0xFF) << 16) | (((data >> 24) & 0xFF) << 8) | (data & 0xFF);
+    let high = (((data >> 10) & 0x1) << 7) | (((data >> 8) & 0x3) << 5) | (((data >> 15) & 0x1) << 4) | ((data >> 11) & 0xF);
+    let value = (high << 8) | ((data >> 32) & 0xFF);
+
+    // the 68000 faults on a misaligned word write, so no working code can decode to one
+    if address & 1 != 0 {
+        return Err(CodeError::OddAddress { code: code.to_string(), address: address as u32 });
+    }
+
+    Ok(Word { address: address as u32, value: value as u16 })
+}
+
+/// Build the `ABCD-EFGH` code for `word`, or `None` when its address is odd
+/// or at or past `$400000`, which the 4th character can't express
+pub fn encode_genesis(word: &Word) -> Option<String> {
+    if word.address >= 0x40_0000 || word.address & 1 != 0 { return None; }
+
+    let (address, value) = (word.address as u64, word.value as u64);
+    let data = ((value & 0xFF) << 32) | (((address >> 8) & 0xFF) << 24) | (((address >> 16) & 0xFF) << 16)
+        | (((value >> 12) & 0x1) << 15) | (((value >> 8) & 0xF) << 11) | (((value >> 15) & 0x1) << 10)
+        | (((value >> 13) & 0x3) << 8) | (address & 0xFF);
+
+    let chars: String = (0..8).rev().map(|i| GENESIS_CONVERSION[((data >> (i * 5)) & 0x1F) as usize]).collect();
+    Some(format!("{}-{}", &chars[..4], &chars[4..]))
+}
+
+/// Whether a Genesis image stores each word low byte first, as `.md` dumps
+/// do; their header reads `ESAG` where a `.bin` reads `SEGA`. `is_md` (the
+/// file's extension) decides when the header is neither
+pub fn is_byte_swapped(rom: &[u8], is_md: bool) -> bool {
+    match rom.get(0x100..0x104) {
+        Some(b"ESAG") => true,
+        Some(b"SEGA") => false,
+        _ => is_md,
+    }
+}
+
+/// Swap each pair of bytes, converting between `.md` and `.bin` order
+pub fn swap_bytes(rom: &mut [u8]) {
+    for word in rom.chunks_exact_mut(2) {
+        word.swap(0, 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    #[test]
+    fn random_words_round_trip() {
+        let mut rng = Rng::new(0x4D44);
+        for _ in 0..2000 {
+            // even addresses inside the 4MB the 4th character allows
+            let word = Word { address: rng.below(0x20_0000) as u32 * 2, value: rng.next() as u16 };
+            let code = encode_genesis(&word).unwrap();
+            assert!(GENESIS_FOURTH.contains(&code.chars().nth(3).unwrap()), "{code}");
+            assert_eq!(parse_genesis(&code).unwrap(), word, "{code}");
+
+            let digits: Vec<char> = normalize(&code).chars().collect();
+            let checked = format!("{code}-{}", check_char(&digits).unwrap());
+            assert_eq!(parse_genesis(&checked).unwrap(), word, "{checked}");
+        }
+    }
+
+    #[test]
+    fn decodes_even_addresses_and_rejects_odd_ones() {
+        assert_eq!(parse_genesis("SCRA-BJX0").unwrap(), Word { address: 0x00_9C76, value: 0x5478 });
+        // one more in the last character sets the address's low bit
+        assert!(matches!(parse_genesis("SCRA-BJX1"), Err(CodeError::OddAddress { address: 0x00_9C77, .. })));
+    }
+
+    #[test]
+    fn checks_a_ninth_character() {
+        assert_eq!(parse_genesis("SCRA-BJX0-V").unwrap(), Word { address: 0x00_9C76, value: 0x5478 });
+        assert!(matches!(parse_genesis("SCRA-BJX0-A"), Err(CodeError::BadCheckChar { found: 'A', expected: 'V', .. })));
+    }
+
+    #[test]
+    fn addresses_the_fourth_character_cant_hold_arent_encoded() {
+        assert_eq!(encode_genesis(&Word { address: 0x40_0000, value: 0 }), None);
+        assert_eq!(encode_genesis(&Word { address: 0x00_9C77, value: 0 }), None);
+    }
+}
@@ -0,0 +1,23 @@
+pub mod codes;
+pub mod checksum;
+pub mod completions;
+pub mod config;
+pub mod console;
+pub mod error;
+pub mod gameboy;
+pub mod genesis;
+pub mod locate;
+pub mod log;
+pub mod mode;
+pub mod nes;
+pub mod patch;
+pub mod progress;
+pub mod report;
+pub mod rom;
+pub mod selftest;
+pub mod snes;
+pub mod tables;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "zip")]
+pub mod zip;
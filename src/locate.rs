@@ -0,0 +1,297 @@
+//! Turning code list entries into file offsets and applying them, the same
+//! way the command line does it.
+
+use std::collections::HashMap;
+
+use crate::{codes::{decode_entry, format_entry}, debug, error::{CodeError, Error}, gameboy, genesis, mode::Mode, nes, patch::{apply, hex, Applied, ApplyStatus, Patch, PatchResult}, rom::{Image, Mapping}, snes::{self, SnesMap}, info, warn};
+
+/// A decoded code and the file offsets it targets, more than one for a NES
+/// code in every bank of a big ROM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Located {
+    pub mode: Mode,
+    pub patch: Patch,
+    pub offsets: Vec<u64>,
+    /// Length of the ROM's header, which codes shouldn't normally land in
+    pub header_len: u64,
+}
+
+/// Decodes code list entries against their systems, detecting each system's
+/// ROM layout the first time it's needed
+pub struct Locator {
+    mode: Mode,
+    snes_map: SnesMap,
+    bank: Option<u64>,
+    all_banks: bool,
+    mappings: HashMap<Mode, Box<dyn Mapping>>,
+}
+
+impl Locator {
+    /// `mode` is the system for untagged codes; `snes_map` and `bank` (a NES
+    /// PRG bank, or Game Boy ROM bank for `$4000-$7FFF`) override layout detection
+    pub fn new(mode: Mode, snes_map: SnesMap, bank: Option<u64>) -> Locator {
+        Locator { mode, snes_map, bank, all_banks: false, mappings: HashMap::new() }
+    }
+
+    /// Write a code that reaches several banks into every one that matches,
+    /// rather than only the first
+    pub fn with_all_banks(self, all_banks: bool) -> Locator {
+        Locator { all_banks, ..self }
+    }
+
+    /// A locator for when there's no ROM to detect layouts from, assuming
+    /// the usual ones: a 16 byte iNES header before the NES PRG, LoROM with
+    /// no copier header (unless `snes_map` says otherwise), and every other
+    /// system's ROM at the start of the file
+    pub fn assumed(mode: Mode, snes_map: SnesMap) -> Locator {
+        let snes_map = if snes_map == SnesMap::Auto { SnesMap::LoRom } else { snes_map };
+        let mappings: [(Mode, Box<dyn Mapping>); 5] = [
+            (Mode::Nintendo, Box::new(nes::Layout::HEADERLESS)),
+            (Mode::SuperNintendo, Box::new(snes::Layout { header: 0, map: snes_map, size: u64::MAX })),
+            (Mode::GameBoy, Box::new(gameboy::Layout { size: u64::MAX, rom_end: gameboy::Layout::GAMEBOY_ROM_END, mbc: gameboy::Mbc::None, bank: None })),
+            (Mode::GameGear, Box::new(gameboy::Layout { size: u64::MAX, rom_end: gameboy::Layout::GAMEGEAR_ROM_END, mbc: gameboy::Mbc::None, bank: None })),
+            (Mode::Genesis, Box::new(genesis::Layout { size: u64::MAX })),
+        ];
+        Locator { mode, snes_map, bank: None, all_banks: false, mappings: mappings.into_iter().collect() }
+    }
+
+    pub fn decode(&self, entry: &str) -> Result<(Mode, Patch), CodeError> {
+        decode_entry(entry, self.mode)
+    }
+
+    /// The mapping steps from `address` to its file offsets for `mode`,
+    /// once `locate` has detected that system's layout
+    pub fn explain(&self, mode: Mode, address: u32) -> Vec<String> {
+        self.mappings.get(&mode).map_or(Vec::new(), |mapping| mapping.explain(address))
+    }
+
+    pub fn locate<I: Image + ?Sized>(&mut self, image: &I, entry: &str) -> Result<Located, Error> {
+        let (mode, patch) = self.decode(entry)?;
+        if !self.mappings.contains_key(&mode) {
+            let detected: Box<dyn Mapping> = match mode {
+                Mode::Nintendo => Box::new(nes::Layout { bank: self.bank, ..nes::Layout::detect(image)? }),
+                Mode::SuperNintendo => Box::new(snes::Layout::detect(image, self.snes_map)?),
+                Mode::GameBoy => Box::new(gameboy::Layout { bank: self.bank, ..gameboy::Layout::detect(image, gameboy::Layout::GAMEBOY_ROM_END)? }),
+                Mode::GameGear => Box::new(gameboy::Layout::detect(image, gameboy::Layout::GAMEGEAR_ROM_END)?),
+                Mode::Genesis => Box::new(genesis::Layout::detect(image)?),
+                // decode() has already turned away every other system
+                _ => unreachable!(),
+            };
+            self.mappings.insert(mode, detected);
+        }
+
+        let mapping = &self.mappings[&mode];
+        let mut offsets = mapping.file_offsets(patch.address);
+        // a run of bytes has to stay in ROM, and in one piece, up to its last byte; Genesis words are checked whole by their layout
+        if patch.value.len() > 1 && mode != Mode::Genesis {
+            let span = patch.value.len() as u64 - 1;
+            let last = patch.address + span as u32;
+            let ends = mapping.file_offsets(last);
+            if !offsets.is_empty() {
+                offsets.retain(|offset| ends.contains(&(offset + span)));
+                if offsets.is_empty() { return Err(CodeError::OutOfRange { code: entry.to_string(), address: last }.into()); }
+            }
+        }
+        match offsets {
+            offsets if !offsets.is_empty() => Ok(Located { mode, patch, offsets, header_len: mapping.header_len() }),
+            _ if gameboy::rom_end(mode).is_some_and(|rom_end| gameboy::is_ram(patch.address, rom_end)) => {
+                Err(CodeError::RamAddress { code: entry.to_string(), address: patch.address, system: mode }.into())
+            },
+            _ => Err(CodeError::OutOfRange { code: entry.to_string(), address: patch.address }.into()),
+        }
+    }
+}
+
+// a code landing at the very start or in the header almost always means a decoding or MODE mistake
+fn warn_header(code: &str, offset: u64, header_len: u64) {
+    if offset == 0 || offset < header_len {
+        warn!("{code} targets file offset {offset:X}, inside the ROM header (is MODE right?)");
+    }
+}
+
+/// Apply every entry of `codes` to `image` in order, stopping at the first
+/// that doesn't decode or map to ROM. A code that reaches several banks goes
+/// into the first whose compare byte matches (every bank matches a code
+/// without one), or every one that matches when the locator is
+/// `with_all_banks`. When no bank matches, `force` writes the first bank
+/// anyway.
+/// `on_code` is called on the calling thread after each code, in list order,
+/// with the results it produced (more than one for a code written in
+/// several banks), so callers can show progress as they go
+pub fn apply_codes_with<I: Image + ?Sized>(
+    locator: &mut Locator, image: &mut I, codes: &[&str], force: bool, mut on_code: impl FnMut(&[PatchResult]),
+) -> Result<Vec<PatchResult>, Error> {
+    let mut results = Vec::with_capacity(codes.len());
+
+    for code in codes {
+        let Located { mode, patch, offsets, header_len } = locator.locate(image, code)?;
+        let check = patch.compare.map_or("none (unchecked)".to_string(), |compare| format!("{compare:X}"));
+        let first = results.len();
+        let name = format_entry(code, locator.mode);
+
+        // across banks, the compare byte is what picks out the right ones, before anything is forced
+        let mut matching = offsets.clone();
+        if let (Some(compare), true) = (patch.compare, offsets.len() > 1) {
+            matching.clear();
+            for &offset in &offsets {
+                let mut byte = [0_u8; 1];
+                image.read_bytes(&mut byte, offset)?;
+                if byte[0] == compare { matching.push(offset); }
+            }
+        }
+        let targets = match (&matching[..], locator.all_banks) {
+            // no bank matches: the first is forced, or gives the code its one mismatch row
+            ([], _) => &offsets[..1],
+            (matching, true) => matching,
+            (matching, false) => &matching[..1],
+        };
+
+        for &offset in targets {
+            warn_header(code, offset, header_len);
+            debug!("{code}: address {:X}, value {}, check {check}, file offset {:X}", patch.address, hex(&patch.value), offset);
+            let result = PatchResult::new(&name, mode, offset, &patch, apply(image, offset, &patch, force)?);
+            match (result.status, patch.compare) {
+                (ApplyStatus::SkippedCompareMismatch { found }, Some(compare)) => {
+                    info!("{name}: ROM holds {found:02X} at {offset:X}, not its compare byte {compare:02X}; skipped");
+                },
+                (ApplyStatus::Forced { found }, Some(compare)) => info!("{name}: ROM holds {found:02X} at {offset:X}, not its compare byte {compare:02X}; written anyway"),
+                _ => {},
+            }
+            results.push(result);
+        }
+        if offsets.len() > 1 {
+            let written = results[first..].iter().filter(|result| matches!(result.status, ApplyStatus::Applied | ApplyStatus::Forced { .. })).count();
+            let which = if locator.all_banks { "" } else { " (only the first that matched; --all-banks writes each)" };
+            info!("{name}: {} banks hold its address, written in {written}{which}", offsets.len());
+        }
+        on_code(&results[first..]);
+    }
+
+    Ok(results)
+}
+
+/// The rows `code` gets in a summary when it's been switched off: one
+/// `Disabled` result per offset it targets, holding the bytes there now.
+/// Nothing is written
+pub fn disabled_code<I: Image + ?Sized>(locator: &mut Locator, image: &I, code: &str) -> Result<Vec<PatchResult>, Error> {
+    skipped_code(locator, image, code, ApplyStatus::Disabled)
+}
+
+/// `disabled_code`, with `status` saying why the code was left out
+pub fn skipped_code<I: Image + ?Sized>(locator: &mut Locator, image: &I, code: &str, status: ApplyStatus) -> Result<Vec<PatchResult>, Error> {
+    let Located { mode, patch, offsets, .. } = locator.locate(image, code)?;
+    let name = format_entry(code, locator.mode);
+    offsets.into_iter().map(|offset| {
+        let mut old = vec![0_u8; patch.value.len()];
+        image.read_bytes(&mut old, offset)?;
+        Ok(PatchResult::new(&name, mode, offset, &patch, Applied { old, status }))
+    }).collect()
+}
+
+/// Check guard code `guard` against `image` without writing it: `None` when
+/// the ROM holds its compare byte (in any bank it reaches), or the byte found
+/// instead. A guard has to have a compare byte
+pub fn check_guard<I: Image + ?Sized>(locator: &mut Locator, image: &I, guard: &str) -> Result<Option<u8>, Error> {
+    let Located { patch, offsets, .. } = locator.locate(image, guard)?;
+    let Some(compare) = patch.compare else { return Err(CodeError::GuardWithoutCompare { code: guard.to_string() }.into()) };
+    let mut found = None;
+    for offset in offsets {
+        let mut byte = [0_u8; 1];
+        image.read_bytes(&mut byte, offset)?;
+        if byte[0] == compare { return Ok(None); }
+        found.get_or_insert(byte[0]);
+    }
+    Ok(found)
+}
+
+/// `apply_codes_with`, for callers that only want the results at the end
+pub fn apply_codes<I: Image + ?Sized>(locator: &mut Locator, image: &mut I, codes: &[&str], force: bool) -> Result<Vec<PatchResult>, Error> {
+    apply_codes_with(locator, image, codes, force, |_| {})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log;
+
+    // where $91D9 lands in each of the 8 PRG banks of `mmc1`
+    const OFFSETS: [u64; 8] = [0x11E9, 0x51E9, 0x91E9, 0xD1E9, 0x1_11E9, 0x1_51E9, 0x1_91E9, 0x1_D1E9];
+    // writes AD at $91D9 where the ROM holds 5A
+    const CODE: &str = "SXSOPOZS";
+
+    // an MMC1 ROM with 128KB of PRG, holding CODE's compare byte in `banks`
+    fn mmc1(banks: &[usize]) -> Vec<u8> {
+        let mut rom = vec![0xEA_u8; 0x10 + 0x2_0000];
+        rom[..16].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 8, 0, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        for &bank in banks { rom[OFFSETS[bank] as usize] = 0x5A; }
+        rom
+    }
+
+    // apply CODE to `rom`, returning each result's bank and status, the banks now holding AD,
+    // and how many warnings that took
+    fn apply_code(rom: &mut Vec<u8>, mut locator: Locator, force: bool) -> (Vec<(usize, ApplyStatus)>, Vec<usize>, usize) {
+        let warned = log::thread_warnings();
+        let results = apply_codes(&mut locator, rom, &[CODE], force).unwrap();
+        let bank = |offset| OFFSETS.iter().position(|&at| at == offset).unwrap();
+        let written = (0..8).filter(|&bank| rom[OFFSETS[bank] as usize] == 0xAD).collect();
+        (results.iter().map(|result| (bank(result.file_offset), result.status)).collect(), written, log::thread_warnings() - warned)
+    }
+
+    fn locator() -> Locator {
+        Locator::new(Mode::Nintendo, SnesMap::Auto, None)
+    }
+
+    #[test]
+    fn a_guard_matches_in_any_bank() {
+        assert_eq!(check_guard(&mut locator(), &mmc1(&[3]), CODE).unwrap(), None);
+        assert_eq!(check_guard(&mut locator(), &mmc1(&[]), CODE).unwrap(), Some(0xEA));
+    }
+
+    #[test]
+    fn a_guard_needs_a_compare_byte() {
+        let err = check_guard(&mut locator(), &mmc1(&[]), "SXIOPO").unwrap_err();
+        assert!(matches!(err, Error::Code(CodeError::GuardWithoutCompare { .. })), "{err}");
+    }
+
+    #[test]
+    fn writes_only_the_first_matching_bank_without_warning() {
+        let mut rom = mmc1(&[2, 5]);
+        assert_eq!(apply_code(&mut rom, locator(), false), (vec![(2, ApplyStatus::Applied)], vec![2], 0));
+        // a single matching bank is no choice at all
+        let mut rom = mmc1(&[6]);
+        assert_eq!(apply_code(&mut rom, locator(), false), (vec![(6, ApplyStatus::Applied)], vec![6], 0));
+    }
+
+    #[test]
+    fn all_banks_writes_every_matching_bank() {
+        let mut rom = mmc1(&[2, 5]);
+        let applied = vec![(2, ApplyStatus::Applied), (5, ApplyStatus::Applied)];
+        assert_eq!(apply_code(&mut rom, locator().with_all_banks(true), false), (applied, vec![2, 5], 0));
+    }
+
+    #[test]
+    fn force_prefers_a_matching_bank() {
+        let mut rom = mmc1(&[5]);
+        assert_eq!(apply_code(&mut rom, locator(), true), (vec![(5, ApplyStatus::Applied)], vec![5], 0));
+    }
+
+    #[test]
+    fn force_writes_the_first_bank_only_when_none_match() {
+        let mut rom = mmc1(&[]);
+        assert_eq!(apply_code(&mut rom, locator(), true), (vec![(0, ApplyStatus::Forced { found: 0xEA })], vec![0], 0));
+        let mut rom = mmc1(&[]);
+        assert_eq!(apply_code(&mut rom, locator(), false), (vec![(0, ApplyStatus::SkippedCompareMismatch { found: 0xEA })], vec![], 0));
+    }
+
+    #[test]
+    fn warns_about_a_code_inside_the_header() {
+        let mut rom = vec![0_u8; 0x8000];
+        let mut locator = Locator::new(Mode::GameBoy, SnesMap::Auto, None);
+        // 001-00F writes 00 at $0100, the Game Boy entry point
+        for (code, warnings) in [("001-00F", 1), ("00A-17B", 0)] {
+            let warned = log::thread_warnings();
+            apply_codes(&mut locator, &mut rom, &[code], false).unwrap();
+            assert_eq!(log::thread_warnings() - warned, warnings, "{code}");
+        }
+    }
+}
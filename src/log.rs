@@ -0,0 +1,100 @@
+//! A minimal logging facade with the `log` crate's macro names. The library
+//! only ever logs through the macros; the binary picks the level once at
+//! startup with [`set_max_level`].
+
+use std::{cell::Cell, fmt, str::FromStr, sync::atomic::{AtomicU8, AtomicUsize, Ordering}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_WARNINGS: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// How many warnings have been logged so far, including ones the level hid
+pub fn warnings() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+/// How many of those warnings this thread logged since it started or last called
+/// [`reset_thread_warnings`], so work spread over threads can be judged job by job
+pub fn thread_warnings() -> usize {
+    THREAD_WARNINGS.get()
+}
+
+pub fn reset_thread_warnings() {
+    THREAD_WARNINGS.set(0);
+}
+
+#[doc(hidden)]
+pub fn log(level: Level, args: fmt::Arguments) {
+    if level == Level::Warn {
+        WARNINGS.fetch_add(1, Ordering::Relaxed);
+        THREAD_WARNINGS.set(THREAD_WARNINGS.get() + 1);
+    }
+    if !enabled(level) { return; }
+    crate::progress::clear();
+
+    match level {
+        Level::Error => eprintln!("error: {args}"),
+        Level::Warn => eprintln!("warning: {args}"),
+        Level::Debug => eprintln!("debug: {args}"),
+        Level::Info | Level::Off => eprintln!("{args}"),
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    /// Accepts a bare level or `RUST_LOG` style directives such as
+    /// `rggp=debug`, taking the last level given
+    fn from_str(s: &str) -> Result<Level, String> {
+        let directive = s.rsplit(',').next().unwrap_or(s);
+        let name = directive.rsplit('=').next().unwrap_or(directive).trim();
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Ok(Level::Off),
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" | "trace" => Ok(Level::Debug),
+            _ => Err(format!("unknown log level '{s}'")),
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => { $crate::log::log($crate::log::Level::Error, format_args!($($arg)+)) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => { $crate::log::log($crate::log::Level::Warn, format_args!($($arg)+)) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => { $crate::log::log($crate::log::Level::Info, format_args!($($arg)+)) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::log::log($crate::log::Level::Debug, format_args!($($arg)+)) };
+}
@@ -1,150 +1,1571 @@
-use std::{fs::{ copy, remove_file, File }, os::unix::fs::FileExt, path::PathBuf};
+use std::{collections::HashMap, env, fs::{ self, copy, remove_file, File }, io::{ self, Read, Write }, path::{Path, PathBuf}, process::{self, exit}, sync::atomic::{AtomicUsize, Ordering}, thread};
 
-use clap::{Parser, ValueEnum};
+use clap::{error::ErrorKind, parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use rggp::{checksum::{checksums, crc32, declared_size, fix_checksums}, completions::{self, Shell}, codes::{decode_entry, format_code, format_entry, parse_cht, parse_code_file, parse_db, select, DbEntry, ListEntry}, config::{self, Value}, debug, gameboy::{self, encode_gameboy}, genesis::{self, encode_genesis, Word}, error, info, error::{Error, EXIT_INVALID_CODE, EXIT_ROM, EXIT_SUCCESS, EXIT_USAGE}, locate::{apply_codes_with, check_guard, disabled_code, skipped_code, Located, Locator}, log::{self, Level}, warn, mode::{Mode, ModeParser}, nes::{self, encode_nes}, patch::{hex, ApplyStatus, Patch, PatchResult}, progress::Progress, report::{tally, write_by_system, write_catalog_csv, write_catalog_text, write_diff, write_summary, SummaryFormat}, rom::{sniff, Image}, selftest::VECTORS, snes::{encode_snes, SnesMap}, tables::{fits, too_wide}};
+#[cfg(feature = "zip")]
+use rggp::zip;
 
-#[derive(Parser, Clone, ValueEnum, Debug)]
-enum Mode {
-    GameBoy, GB,
-    GameGear, GG,
-    MasterSystem, SMS,
-    Genesis, SG,
-    MegaDrive, MD,
-    Nintendo, NES,
-    SuperNintendo, SNES,
+#[derive(Parser, Debug, Clone)]
+#[command(version, about = "Patch a ROM with Game Genie codes", long_about = None, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(value_name = "CODES", env = "RGGP_CODES", help = "+-separated codes, each optionally tagged with its system (e.g. NES:SXIOPO)", required_unless_present_any = ["config", "list_modes", "code", "codes_file", "db"])]
+    codes: Option<String>,
+    #[arg(long, value_name = "CODE", help = "Add a code to the list; repeatable, and with it CODES can be left out (or given as --codes)")]
+    code: Vec<String>,
+    #[arg(long, value_name = "PATH", help = "Add the codes in a file (- for stdin), one cheat per line, optionally labelled: 'Infinite Lives: SXIOPO'")]
+    codes_file: Option<PathBuf>,
+    #[arg(long, value_name = "PATH", help = "Read cheats from a database file of GameID|CheatName|Codes|Mode lines")]
+    db: Option<PathBuf>,
+    #[arg(long, value_name = "CHEAT", requires = "db", help = "Add the --db cheats matching GameID:CheatName, or every cheat of a game ID or name; repeatable")]
+    select: Vec<String>,
+    #[arg(value_name = "MODE", env = "RGGP_MODE", help = "ROM mode selection", value_parser = ModeParser, required_unless_present_any = ["config", "list_modes"])]
+    mode: Option<Mode>,
+    #[arg(value_name = "INPUT", env = "RGGP_INPUT", help = "Path to input ROM file, or a directory of them", required_unless_present_any = ["count", "config", "list_modes", "buffer"])]
+    rom_in: Option<PathBuf>,
+    #[arg(value_name = "OUTPUT", env = "RGGP_OUTPUT", help = "Desired output path for patched ROM (a directory when INPUT is one)", required_unless_present_any = ["count", "stdout", "config", "outputs", "list_modes", "out_dir", "buffer", "dry_run"])]
+    rom_out: Option<PathBuf>,
+    #[arg(short = 'o', long = "output", value_name = "OUTPUT", conflicts_with = "stdout", help = "Write the patched ROM here too; repeat for more copies")]
+    outputs: Vec<PathBuf>,
+    #[arg(long, value_name = "DIR", conflicts_with = "stdout", help = "Put OUTPUT and every -o under DIR, creating it; without OUTPUT, the ROM keeps INPUT's file name")]
+    out_dir: Option<PathBuf>,
+    #[arg(long, value_name = "HEX", conflicts_with_all = ["rom_in", "rom_out", "outputs", "out_dir", "stdout"], help = "Patch these bytes (hex, or - for stdin) instead of a ROM file, and print the result as hex")]
+    buffer: Option<String>,
+    #[arg(long, value_name = "PATH", help = "Read mode, codes, paths and flags from a TOML file; command line arguments take precedence")]
+    config: Option<PathBuf>,
+    #[arg(long, exclusive = true, help = "Print every MODE with its aliases and code format, then exit")]
+    list_modes: bool,
+    #[arg(long, help = "Print how many codes are valid (and would change INPUT, if given), then exit")]
+    count: bool,
+    #[arg(long, conflicts_with_all = ["stdout", "count", "buffer", "exec"], help = "Patch INPUT in memory and print each change as offset: old -> new, writing nothing")]
+    dry_run: bool,
+    #[arg(long, value_name = "N", default_value_t = 1024, help = "Refuse to apply more than N codes")]
+    max_codes: usize,
+    #[arg(long, conflicts_with = "rom_out", help = "Write the patched ROM to stdout instead of OUTPUT")]
+    stdout: bool,
+    #[arg(long, value_name = "N", help = "Resolve NES codes into 16KB PRG bank N instead of the mirrored default, or Game Boy $4000-$7FFF codes into ROM bank N (only some mapper games need this)")]
+    bank: Option<u64>,
+    #[arg(long, conflicts_with = "bank", help = "Write NES and Game Boy codes that several ROM banks match into only the first of them (the default)")]
+    first_match: bool,
+    #[arg(long, conflicts_with_all = ["bank", "first_match"], help = "Write NES and Game Boy codes that reach several ROM banks into every bank that matches")]
+    all_banks: bool,
+    #[arg(long, value_enum, default_value_t = SnesMap::Auto, help = "SNES cartridge memory map")]
+    snes_map: SnesMap,
+    #[arg(long, help = "Refuse to patch unless INPUT's stored checksum matches its contents (SNES, Genesis, Game Boy)")]
+    verify_checksum: bool,
+    #[arg(long, value_name = "HEX", value_parser = parse_crc, help = "Refuse to patch unless INPUT's CRC32 is HEX, to pin a ROM revision")]
+    expect_crc: Option<u32>,
+    #[arg(long, help = "Rewrite the patched ROM's stored checksums to match it (SNES, Genesis, Game Boy)")]
+    fix_checksum: bool,
+    #[arg(long, help = "Warn when INPUT's size differs from the size its header declares (SNES, Genesis, Game Boy)")]
+    check_size: bool,
+    #[cfg(feature = "zip")]
+    #[arg(long, value_name = "NAME", help = "Which file to patch when INPUT is a zip archive holding several ROMs")]
+    entry: Option<String>,
+    #[cfg(feature = "zip")]
+    #[arg(long, conflicts_with = "stdout", help = "Write each OUTPUT as a zip archive holding the patched ROM")]
+    zip_output: bool,
+    #[arg(long, conflicts_with = "stdout", help = "When OUTPUT already holds an identical copy of INPUT, write just the patched bytes into it instead of copying INPUT over")]
+    no_copy: bool,
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, help = "Zero-pad a short ROM up to SIZE bytes (e.g. 512K, 0x80000) before patching")]
+    pad: Option<u64>,
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, help = "Cut an overdumped ROM down to SIZE bytes before patching")]
+    trim: Option<u64>,
+    #[arg(long, value_name = "START:END", value_parser = parse_region, conflicts_with = "buffer", help = "Write only bytes START up to (not including) END of the patched ROM to OUTPUT")]
+    extract_region: Option<(u64, u64)>,
+    #[arg(long, help = "Write codes whose compare byte doesn't match INPUT, and skip checking that INPUT looks like a MODE ROM")]
+    force: bool,
+    #[arg(long, help = "Treat warnings (a code in the ROM header, no checksum to verify) as errors, and write nothing")]
+    strict: bool,
+    #[arg(long, help = "Print how each code's address becomes a file offset: header, mapping or mirroring, bank and copier header")]
+    explain_offsets: bool,
+    #[arg(short, long, help = "Print debug detail: decoded codes, file offsets, stored and computed checksums")]
+    verbose: bool,
+    #[arg(short, long, conflicts_with = "verbose", help = "Only print errors")]
+    quiet: bool,
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "stdout", help = "Print a per-patch summary to stdout")]
+    format_summary: Option<SummaryFormat>,
+    #[arg(long, conflicts_with_all = ["format_summary", "json", "stdout"], help = "Shorthand for --format-summary csv")]
+    csv: bool,
+    #[arg(long, conflicts_with_all = ["format_summary", "stdout"], help = "Shorthand for --format-summary json")]
+    json: bool,
+    #[arg(long, conflicts_with = "stdout", help = "Group the summary by system, with counts for each (text unless a format is given)")]
+    by_system: bool,
+    #[arg(long, value_name = "PATH", help = "Write the summary to PATH instead of stdout (text unless a format is given)")]
+    summary_file: Option<PathBuf>,
+    #[arg(long, conflicts_with_all = ["stdout", "count", "buffer", "dry_run"], help = "Record INPUT, its CRC32, MODE, the codes and each patch in OUTPUT.manifest.toml, a --config file that reproduces OUTPUT")]
+    manifest: bool,
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["stdout", "count", "buffer"], help = "Run COMMAND once OUTPUT is patched, e.g. an emulator; {} is replaced by OUTPUT's path, which is otherwise added at the end")]
+    exec: Option<String>,
 }
 
-const NES_CONVERSION: [char; 16] = ['A', 'P', 'Z', 'L', 'G', 'I', 'T', 'Y', 'E', 'O', 'X', 'U', 'K', 'S', 'V', 'N'];
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Print what each code decodes to (address, value, compare) without touching a ROM
+    Decode {
+        #[arg(value_name = "CODES", help = "+-separated codes, each optionally tagged with its system (e.g. NES:SXIOPO)")]
+        codes: String,
+        #[arg(value_name = "MODE", help = "System for untagged codes; several, comma-separated (NES,GB), decode the list once for each", value_parser = ModeParser, value_delimiter = ',', num_args = 1, required = true)]
+        modes: Vec<Mode>,
+    },
+    /// Print every code's patch as an aligned table sorted by address, to review a cheat set for overlaps
+    Table {
+        #[arg(value_name = "CODES", help = "+-separated codes, each optionally tagged with its system (e.g. NES:SXIOPO)")]
+        codes: String,
+        #[arg(value_name = "MODE", help = "System for untagged codes", value_parser = ModeParser)]
+        mode: Mode,
+        #[arg(value_name = "INPUT", help = "ROM to take file offsets from; without it the usual layout for each system is assumed")]
+        rom_in: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = SnesMap::Auto, help = "SNES cartridge memory map")]
+        snes_map: SnesMap,
+    },
+    /// List a NES or Game Boy ROM's 16KB banks, their file offsets and the CPU window each maps to, to pick a --bank
+    Banks {
+        #[arg(value_name = "INPUT", help = "ROM to read the banks of; it isn't modified")]
+        rom_in: PathBuf,
+        #[arg(value_name = "MODE", help = "ROM mode selection (NES or GB)", value_parser = ModeParser)]
+        mode: Mode,
+    },
+    /// Convert NES and Game Boy codes between their short form and the longer one with a compare byte (read from INPUT)
+    Convert {
+        #[arg(value_name = "CODES", help = "+-separated codes, each optionally tagged with its system (e.g. NES:SXIOPO)")]
+        codes: String,
+        #[arg(value_name = "MODE", help = "System for untagged codes", value_parser = ModeParser)]
+        mode: Mode,
+        #[arg(value_name = "INPUT", help = "ROM to read the compare byte from when lengthening a code")]
+        rom_in: Option<PathBuf>,
+        #[arg(long, value_enum, value_name = "FORM", help = "Form to convert every code to, instead of switching each to the other")]
+        to: Option<Form>,
+    },
+    /// Decode every code in a code file into a catalog of label, code, address, value and compare, without a ROM
+    Catalog {
+        #[arg(value_name = "PATH", help = "Code file (- for stdin), one cheat per line, optionally labelled: 'Infinite Lives: SXIOPO'")]
+        codes_file: PathBuf,
+        #[arg(value_name = "MODE", help = "System for untagged codes", value_parser = ModeParser)]
+        mode: Mode,
+        #[arg(long, help = "Write CSV instead of tab-separated text")]
+        csv: bool,
+        #[arg(long, help = "Stop at the first code that doesn't decode instead of giving it an error column")]
+        strict: bool,
+    },
+    /// Decode and re-encode known codes for every supported system, to check the build
+    Selftest,
+    /// Print a completion script for SHELL, to save into its completions directory
+    Completions {
+        #[arg(value_name = "SHELL", value_enum)]
+        shell: Shell,
+    },
+    /// Build a code that writes VALUE (or WORD) at ADDRESS (numbers are decimal, or hex with a 0x or $ prefix)
+    Encode {
+        #[arg(long, value_name = "ADDRESS", value_parser = parse_number, help = "CPU address to patch (for SNES, the bus address such as 0x008E28, not a file offset)")]
+        address: u32,
+        #[arg(long, value_name = "VALUE", value_parser = parse_number, required_unless_present = "word", help = "Byte to write")]
+        value: Option<u32>,
+        #[arg(long, value_name = "WORD", value_parser = parse_number, conflicts_with_all = ["value", "compare"], help = "16-bit value to write (Genesis)")]
+        word: Option<u32>,
+        #[arg(long, value_name = "VALUE", value_parser = parse_number, help = "Only write when the ROM already holds this byte")]
+        compare: Option<u32>,
+        #[arg(value_name = "MODE", help = "System to build the code for", value_parser = ModeParser)]
+        mode: Mode,
+    },
+}
 
-#[derive(Parser, Debug)]
-#[command(version, about = "Patch a ROM with Game Genie codes", long_about = None)]
-struct Args {
-    #[arg(value_name = "CODES")]
-    codes: String,
-    #[arg(value_name = "MODE", help = "ROM mode selection")]
-    mode: Mode,
-    #[arg(value_name = "INPUT", help = "Path to input ROM file")]
-    rom_in: PathBuf,
-    #[arg(value_name = "OUTPUT", help = "Desired output path for patched ROM")]
-    rom_out: PathBuf,
-}
-
-fn parse_nes(code: &str, file: &File, base_offset: u64) -> (u64, u8) {
-    if code.len() == 6 { //  unchecked code variation
-        // convert code chars to predesignated usize values
-        let data_hex: Vec<u8> = code.chars().map(|i| {
-            match NES_CONVERSION.iter().position(|&c| c == i) {
-                Some(x) => x as u8,
-                None => {
-                    eprintln!("Invalid code input");
-                    std::process::exit(32);
-                }
+/// The two forms of a NES or Game Boy code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Form {
+    /// 6 letters or digits, always written
+    Short,
+    /// 8 NES letters or 9 Game Boy digits, with a compare byte
+    Long,
+}
+
+// a byte count: a number as parse_number reads it, or decimal with a K or M suffix (512K, 4M)
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        _ => return parse_number(s).map(u64::from),
+    };
+    let n = number.parse::<u64>().map_err(|err| err.to_string())?;
+    n.checked_mul(unit).ok_or_else(|| format!("{s} is too large"))
+}
+
+// a START:END byte range, END exclusive, each a number as parse_number reads it
+fn parse_region(s: &str) -> Result<(u64, u64), String> {
+    let Some((start, end)) = s.split_once(':') else { return Err("expected START:END".to_string()) };
+    let (start, end) = (parse_number(start)? as u64, parse_number(end)? as u64);
+    if start >= end { return Err(format!("START {start:#X} has to be before END {end:#X}")); }
+    Ok((start, end))
+}
+
+// a CRC32 as ROM databases list it: 8 hex digits, with or without 0x
+fn parse_crc(s: &str) -> Result<u32, String> {
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    u32::from_str_radix(hex, 16).map_err(|err| err.to_string())
+}
+
+fn parse_number(s: &str) -> Result<u32, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).or_else(|| s.strip_prefix('$')) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|err| err.to_string())
+}
+
+impl Args {
+    fn fail(kind: ErrorKind, message: String) -> ! {
+        Args::command().error(kind, message).exit()
+    }
+
+    // fill in whatever wasn't given on the command line, or in an RGGP_ environment variable, from the --config file
+    fn merge_config(&mut self, matches: &ArgMatches) {
+        let Some(path) = self.config.clone() else { return };
+        let table = fs::read_to_string(&path).map_err(|err| err.to_string())
+            .and_then(|src| config::parse(&src).map_err(|err| format!("line {}: {}", err.line, err.message)))
+            .unwrap_or_else(|err| Args::fail(ErrorKind::InvalidValue, format!("{}: {err}", path.display())));
+        // relative paths in the file are relative to the file itself
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        for (key, value) in table {
+            let id = match key.as_str() {
+                "input" => "rom_in",
+                "output" => "rom_out",
+                key => key,
+            }.replace('-', "_");
+            let known = id != "config" && Args::command().get_arguments().any(|arg| arg.get_id() == id.as_str());
+            if known && matches!(matches.value_source(&id), Some(ValueSource::CommandLine | ValueSource::EnvVariable)) { continue; }
+            // -o on the command line replaces the file's outputs as well
+            if id == "rom_out" && matches.value_source("outputs") == Some(ValueSource::CommandLine) { continue; }
+
+            let merged = match id.as_str() {
+                "codes" => match value {
+                    Value::Array(codes) => codes.into_iter().map(config_string).collect::<Result<Vec<_>, _>>().map(|codes| codes.join("+")),
+                    value => config_string(value),
+                }.map(|codes| self.codes = Some(codes)),
+                "mode" => config_string(value).and_then(|mode| mode.parse()).map(|mode| self.mode = Some(mode)),
+                "rom_in" => config_string(value).map(|input| self.rom_in = Some(dir.join(input))),
+                "exec" => config_string(value).map(|command| self.exec = Some(command)),
+                "codes_file" => config_string(value).map(|file| self.codes_file = Some(dir.join(file))),
+                "summary_file" => config_string(value).map(|file| self.summary_file = Some(dir.join(file))),
+                "db" => config_string(value).map(|file| self.db = Some(dir.join(file))),
+                "select" => match value {
+                    Value::Array(selectors) => selectors.into_iter().map(config_string).collect::<Result<Vec<_>, _>>(),
+                    value => config_string(value).map(|selector| vec![selector]),
+                }.map(|selectors| self.select = selectors),
+                "out_dir" => config_string(value).map(|out_dir| self.out_dir = Some(dir.join(out_dir))),
+                "rom_out" => match value {
+                    Value::Array(outputs) => outputs.into_iter().map(config_string).collect::<Result<Vec<_>, _>>()
+                        .map(|outputs| self.outputs = outputs.into_iter().map(|output| dir.join(output)).collect()),
+                    value => config_string(value).map(|output| self.rom_out = Some(dir.join(output))),
+                },
+                "snes_map" => config_string(value).and_then(|map| SnesMap::from_str(&map, true)).map(|map| self.snes_map = map),
+                "format_summary" => config_string(value).and_then(|format| SummaryFormat::from_str(&format, true)).map(|format| self.format_summary = Some(format)),
+                "max_codes" => match value {
+                    Value::Integer(n) if n >= 0 => {
+                        self.max_codes = n as usize;
+                        Ok(())
+                    }
+                    _ => Err("expected a non-negative integer".to_string()),
+                },
+                "bank" => match value {
+                    Value::Integer(n) if n >= 0 => {
+                        self.bank = Some(n as u64);
+                        Ok(())
+                    }
+                    _ => Err("expected a non-negative integer".to_string()),
+                },
+                "pad" | "trim" => match value {
+                    Value::Integer(n) if n >= 0 => Ok(n as u64),
+                    Value::String(size) => parse_size(&size),
+                    _ => Err("expected a size".to_string()),
+                }.map(|size| if id == "pad" { self.pad = Some(size) } else { self.trim = Some(size) }),
+                "count" => config_bool(value).map(|flag| self.count = flag),
+                "manifest" => config_bool(value).map(|flag| self.manifest = flag),
+                "dry_run" => config_bool(value).map(|flag| self.dry_run = flag),
+                "stdout" => config_bool(value).map(|flag| self.stdout = flag),
+                "verify_checksum" => config_bool(value).map(|flag| self.verify_checksum = flag),
+                "expect_crc" => config_string(value).and_then(|crc| parse_crc(&crc)).map(|crc| self.expect_crc = Some(crc)),
+                "extract_region" => config_string(value).and_then(|region| parse_region(&region)).map(|region| self.extract_region = Some(region)),
+                "fix_checksum" => config_bool(value).map(|flag| self.fix_checksum = flag),
+                "check_size" => config_bool(value).map(|flag| self.check_size = flag),
+                "explain_offsets" => config_bool(value).map(|flag| self.explain_offsets = flag),
+                "first_match" => config_bool(value).map(|flag| self.first_match = flag),
+                "all_banks" => config_bool(value).map(|flag| self.all_banks = flag),
+                "strict" => config_bool(value).map(|flag| self.strict = flag),
+                "force" => config_bool(value).map(|flag| self.force = flag),
+                "no_copy" => config_bool(value).map(|flag| self.no_copy = flag),
+                "verbose" => config_bool(value).map(|flag| self.verbose = flag),
+                "quiet" => config_bool(value).map(|flag| self.quiet = flag),
+                "csv" => config_bool(value).map(|flag| self.csv = flag),
+                "json" => config_bool(value).map(|flag| self.json = flag),
+                "by_system" => config_bool(value).map(|flag| self.by_system = flag),
+                #[cfg(feature = "zip")]
+                "entry" => config_string(value).map(|entry| self.entry = Some(entry)),
+                #[cfg(feature = "zip")]
+                "zip_output" => config_bool(value).map(|flag| self.zip_output = flag),
+                _ if !known => Err("unknown key".to_string()),
+                _ => Err("can't be set from a config file".to_string()),
+            };
+            if let Err(err) = merged {
+                Args::fail(ErrorKind::InvalidValue, format!("{}: {key}: {err}", path.display()));
             }
-        }).collect();
-
-        // bit manupulation of u8s
-        /*
-            0000 1111 2222 3333 4444 5555
-            -333 4555 1222 3444 0111 5000 
-         */
-
-        let mut res_data: [u8; 6] = [0, 0, 0, 0, 0, 0];
-        
-        res_data[0] = (data_hex[3] & 0b0111)                          as u8;
-        res_data[1] = (data_hex[5] & 0b0111) + (data_hex[4] & 0b1000) as u8;
-        res_data[2] = (data_hex[2] & 0b0111) + (data_hex[1] & 0b1000) as u8;
-        res_data[3] = (data_hex[4] & 0b0111) + (data_hex[3] & 0b1000) as u8;
-        res_data[4] = (data_hex[1] & 0b0111) + (data_hex[0] & 0b1000) as u8;
-        res_data[5] = (data_hex[0] & 0b0111) + (data_hex[5] & 0b1000) as u8;
-    
-        let address: u64 = {
-            ((res_data[0] as u64) << (4 * 3)) +
-            ((res_data[1] as u64) << (4 * 2)) +
-            ((res_data[2] as u64) << (4 * 1)) +
-            ((res_data[3] as u64) << (4 * 0)) 
+        }
+    }
+
+    // move every output under --out-dir, naming the ROM after INPUT when no OUTPUT was given
+    fn apply_out_dir(&mut self) -> Result<(), Error> {
+        let Some(out_dir) = self.out_dir.clone() else { return Ok(()) };
+        fs::create_dir_all(&out_dir).map_err(with_path("create", &out_dir))?;
+
+        self.rom_out = match (self.rom_out.take(), &self.rom_in) {
+            (Some(rom_out), _) => Some(out_dir.join(rom_out)),
+            (None, _) if !self.outputs.is_empty() => None,
+            // a directory INPUT writes straight into --out-dir
+            (None, Some(rom_in)) if rom_in.is_dir() => Some(out_dir.clone()),
+            (None, Some(rom_in)) => rom_in.file_name().map(|name| out_dir.join(name)),
+            (None, None) => None,
         };
+        self.outputs = self.outputs.iter().map(|output| out_dir.join(output)).collect();
+        Ok(())
+    }
 
-        return (address + base_offset, (res_data[4] << 4) + res_data[5]);
-    } else if code.len() == 8 { // checked code variation
-        let data_hex: Vec<u8> = code.chars().map(|i| {
-            match NES_CONVERSION.iter().position(|&c| c == i) {
-                Some(x) => x as u8,
-                None => {
-                    eprintln!("Invalid code input");
-                    std::process::exit(32);
-                }
+    fn summary_format(&self) -> Option<SummaryFormat> {
+        match (self.csv, self.json) {
+            (true, _) => Some(SummaryFormat::Csv),
+            (_, true) => Some(SummaryFormat::Json),
+            _ if self.by_system || self.summary_file.is_some() => Some(self.format_summary.unwrap_or(SummaryFormat::Text)),
+            _ => self.format_summary,
+        }
+    }
+}
+
+fn config_string(value: Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        _ => Err("expected a string".to_string()),
+    }
+}
+
+fn config_bool(value: Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+// one line per code: code, system, address, value, compare ("--" when there is none), and the bank (or RAM) for Game Boy codes;
+// with several modes, the list is decoded under each in turn below a [MODE] heading
+fn decode(codes: &str, modes: &[Mode]) -> ! {
+    let mut invalid = false;
+
+    for (i, &default) in modes.iter().enumerate() {
+        if modes.len() > 1 { println!("{}[{default}]", if i > 0 { "\n" } else { "" }); }
+        if !decode_list(codes, default, modes.len() > 1) { invalid = true; }
+    }
+
+    exit(if invalid { EXIT_INVALID_CODE } else { EXIT_SUCCESS })
+}
+
+// print `codes` decoded with `default` for untagged ones, returning whether they all decoded
+fn decode_list(codes: &str, default: Mode, name_mode: bool) -> bool {
+    let mut valid = true;
+
+    for entry in codes.split('+') {
+        let decoded = decode_entry(entry, default).map(|(mode, patch)| {
+            let compare = patch.compare.map_or("--".to_string(), |compare| format!("{compare:02X}"));
+            let fields = format!("{:06X}\t{}\t{compare}", patch.address, hex(&patch.value));
+            let fields = match (mode, gameboy::rom_end(mode)) {
+                (_, Some(rom_end)) if gameboy::is_ram(patch.address, rom_end) => format!("{fields}\tRAM (can't be patched into a ROM)"),
+                (Mode::GameBoy, _) => match gameboy::bank(patch.address) {
+                    Some(bank) => format!("{fields}\tbank {bank}"),
+                    None => format!("{fields}\tswitchable bank"),
+                },
+                _ => fields,
+            };
+            (mode, fields)
+        });
+
+        match decoded {
+            Ok((mode, fields)) => println!("{}\t{mode}\t{fields}", format_entry(entry, default)),
+            Err(err) if name_mode => {
+                error!("as {default}: {err}");
+                valid = false;
+            },
+            Err(err) => {
+                error!("{err}");
+                valid = false;
             }
-        }).collect();
-
-        // bit manupulation of u8s
-        /*
-            0000 1111 2222 3333 4444 5555 6666 7777
-            -333 4555 1222 3444 0111 7000 6777 5666
-         */
-
-        let mut res_data: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
-        
-        res_data[0] = (data_hex[3] & 0b0111)                          as u8;
-        res_data[1] = (data_hex[5] & 0b0111) + (data_hex[4] & 0b1000) as u8;
-        res_data[2] = (data_hex[2] & 0b0111) + (data_hex[1] & 0b1000) as u8;
-        res_data[3] = (data_hex[4] & 0b0111) + (data_hex[3] & 0b1000) as u8;
-        res_data[4] = (data_hex[1] & 0b0111) + (data_hex[0] & 0b1000) as u8;
-        res_data[5] = (data_hex[0] & 0b0111) + (data_hex[7] & 0b1000) as u8;
-        res_data[6] = (data_hex[7] & 0b0111) + (data_hex[6] & 0b1000) as u8;
-        res_data[7] = (data_hex[6] & 0b0111) + (data_hex[5] & 0b1000) as u8;
-    
-        let address: u16 = {
-            ((res_data[0] as u16) << (4 * 3)) +
-            ((res_data[1] as u16) << (4 * 2)) +
-            ((res_data[2] as u16) << (4 * 1)) +
-            ((res_data[3] as u16) << (4 * 0)) 
+        }
+    }
+
+    valid
+}
+
+fn table(codes: &str, default: Mode, rom_in: Option<&Path>, snes_map: SnesMap) -> ! {
+    let rom = rom_in.map(|path| fs::read(path).unwrap_or_else(|err| {
+        error!("Unable to read {}: {err}", path.display());
+        exit(EXIT_ROM);
+    }));
+    let (mut locator, image) = match rom {
+        Some(rom) => (Locator::new(default, snes_map, None), rom),
+        None => (Locator::assumed(default, snes_map), Vec::new()),
+    };
+
+    let mut invalid = false;
+    let mut rows = Vec::new();
+    for entry in codes.split('+') {
+        match locator.locate(&image, entry) {
+            Ok(Located { mode, patch, offsets, .. }) => {
+                let offsets: Vec<String> = offsets.iter().map(|offset| format!("{offset:X}")).collect();
+                let compare = patch.compare.map_or("--".to_string(), |compare| format!("{compare:02X}"));
+                rows.push((mode, patch.address..patch.address + patch.value.len() as u32, [format_entry(entry, default), mode.to_string(), format!("{:06X}", patch.address), offsets.join(","), hex(&patch.value), compare]));
+            },
+            Err(err) => {
+                error!("{err}");
+                invalid = true;
+            }
+        }
+    }
+    rows.sort_by_key(|(mode, span, _)| (span.start, mode.to_string()));
+
+    let header = ["CODE", "SYSTEM", "ADDRESS", "OFFSET", "VALUE", "COMPARE"].map(String::from);
+    let mut widths = header.clone().map(|column| column.len());
+    for (_, _, row) in &rows {
+        for (width, field) in widths.iter_mut().zip(row) { *width = (*width).max(field.len()); }
+    }
+    let line = |row: &[String]| row.iter().zip(widths).map(|(field, width)| format!("{field:width$}")).collect::<Vec<_>>().join("  ");
+
+    println!("{}", line(&header).trim_end());
+    for (i, (mode, span, row)) in rows.iter().enumerate() {
+        // two codes writing the same byte can't both take effect, though a word code may start a byte before the other
+        let overlaps = rows.iter().enumerate().any(|(j, (other, at, _))| i != j && other == mode && at.start < span.end && span.start < at.end);
+        println!("{}{}", line(row).trim_end(), if overlaps { "  overlaps" } else { "" });
+    }
+
+    exit(if invalid { EXIT_INVALID_CODE } else { EXIT_SUCCESS })
+}
+
+// one row per 16KB bank: its number, file offsets and where the CPU sees it
+fn banks(rom_in: &Path, mode: Mode) -> ! {
+    let fail = |err: Error| -> ! {
+        error!("{err}");
+        exit(err.exit_code());
+    };
+    let rom = File::open(rom_in).map_err(with_path("read", rom_in)).unwrap_or_else(|err| fail(err));
+    let size = rom.size().map_err(with_path("read", rom_in)).unwrap_or_else(|err| fail(err));
+
+    let (start, bank_count, windows): (u64, u64, Box<dyn Fn(u64) -> String>) = match mode {
+        Mode::Nintendo => {
+            let layout = nes::Layout::detect(&rom).map_err(with_path("read", rom_in)).unwrap_or_else(|err| fail(err));
+            let prg = layout.prg_size.unwrap_or(size.saturating_sub(layout.prg_start));
+            println!("PRG: {prg:#X} bytes from file offset {:#X}{}", layout.prg_start, if layout.prg_size.is_none() { " (no iNES header, so the rest of the file)" } else { "" });
+            let count = prg.div_ceil(0x4000);
+            (layout.prg_start, count, Box::new(move |bank| match count {
+                1 => "$8000-$BFFF, mirrored at $C000-$FFFF".to_string(),
+                2 if bank == 0 => "$8000-$BFFF".to_string(),
+                2 => "$C000-$FFFF".to_string(),
+                _ if bank + 1 == count => "$8000-$FFFF as the mapper switches (often fixed at $C000-$FFFF)".to_string(),
+                _ => "$8000-$FFFF as the mapper switches".to_string(),
+            }))
+        },
+        Mode::GameBoy => {
+            let layout = gameboy::Layout::detect(&rom, gameboy::Layout::GAMEBOY_ROM_END).map_err(with_path("read", rom_in)).unwrap_or_else(|err| fail(err));
+            println!("ROM: {size:#X} bytes, {:?} controller", layout.mbc);
+            (0, size.div_ceil(0x4000), Box::new(move |bank| match bank {
+                0 => "$0000-$3FFF (fixed)".to_string(),
+                bank if layout.mbc.can_switch_to(bank) && layout.mbc != gameboy::Mbc::None => "$4000-$7FFF as the controller switches".to_string(),
+                1 => "$4000-$7FFF".to_string(),
+                _ => "none, the controller can't switch it in".to_string(),
+            }))
+        },
+        _ => {
+            error!("Listing {mode} banks isn't supported; only NES and Game Boy ROMs are banked in 16KB pieces rggp can resolve");
+            exit(EXIT_USAGE);
+        },
+    };
+
+    let rows: Vec<[String; 3]> = (0..bank_count).map(|bank| {
+        let first = start + bank * 0x4000;
+        let last = (first + 0x3FFF).min(size.max(first + 1) - 1);
+        [bank.to_string(), format!("{first:X}-{last:X}"), windows(bank)]
+    }).collect();
+    let header = ["BANK", "FILE OFFSETS", "CPU WINDOW"].map(String::from);
+    let mut widths = header.clone().map(|column| column.len());
+    for row in &rows {
+        for (width, field) in widths.iter_mut().zip(row) { *width = (*width).max(field.len()); }
+    }
+    let line = |row: &[String]| row.iter().zip(widths).map(|(field, width)| format!("{field:width$}")).collect::<Vec<_>>().join("  ");
+
+    println!("{}", line(&header).trim_end());
+    for row in &rows {
+        println!("{}", line(row).trim_end());
+    }
+    exit(EXIT_SUCCESS)
+}
+
+// every code of a code file decoded, a row each, with codes that don't decode kept as an error column
+fn catalog(path: &Path, default: Mode, csv: bool, strict: bool) -> ! {
+    let src = if path.as_os_str() == "-" { io::read_to_string(io::stdin()) } else { fs::read_to_string(path) };
+    let src = src.unwrap_or_else(|err| {
+        error!("Unable to read {}: {err}", path.display());
+        exit(EXIT_ROM);
+    });
+    let entries = parse_code_file(&src);
+    let catalog: Vec<(&ListEntry, Result<(Mode, Patch), _>)> = entries.iter().map(|entry| (entry, decode_entry(&entry.code, default))).collect();
+
+    if let (true, Some((_, Err(err)))) = (strict, catalog.iter().find(|(_, decoded)| decoded.is_err())) {
+        error!("{err}");
+        exit(EXIT_INVALID_CODE);
+    }
+    let mut report = Vec::new();
+    let written = match csv {
+        true => write_catalog_csv(&mut report, &catalog, default),
+        false => write_catalog_text(&mut report, &catalog, default),
+    };
+    if let Err(err) = written.and_then(|()| io::stdout().lock().write_all(&report)) {
+        error!("{err}");
+        exit(EXIT_ROM);
+    }
+    exit(EXIT_SUCCESS)
+}
+
+// one line per code: the code as given, then in the other form; lengthening takes the compare byte from the ROM
+fn convert(codes: &str, default: Mode, rom_in: Option<&Path>, to: Option<Form>) -> ! {
+    let rom = rom_in.map(|path| fs::read(path).unwrap_or_else(|err| {
+        error!("Unable to read {}: {err}", path.display());
+        exit(EXIT_ROM);
+    }));
+    let mut locator = Locator::new(default, SnesMap::Auto, None);
+
+    let mut invalid = false;
+    for entry in codes.split('+') {
+        let converted = decode_entry(entry, default).map_err(Error::from).and_then(|(mode, mut patch)| {
+            if !matches!(mode, Mode::Nintendo | Mode::GameBoy | Mode::GameGear) {
+                return Err(Error::Rom(format!("{mode} codes only come in one form, there's nothing to convert {} to", format_entry(entry, default))));
+            }
+            let form = to.unwrap_or(if patch.compare.is_some() { Form::Short } else { Form::Long });
+            match form {
+                Form::Short => patch.compare = None,
+                Form::Long if patch.compare.is_some() => {},
+                Form::Long => {
+                    let Some(rom) = &rom else { return Err(Error::Rom(format!("{} has no compare byte; give INPUT to read one from", format_entry(entry, default)))) };
+                    let Located { offsets, .. } = locator.locate(rom, entry)?;
+                    let mut bytes = offsets.iter().map(|&offset| rom.get(offset as usize).copied().unwrap_or(0));
+                    let first = bytes.next().unwrap_or(0);
+                    if bytes.any(|byte| byte != first) {
+                        warn!("{} reaches {} banks that hold different bytes; using the first bank's, {first:02X}", format_entry(entry, default), offsets.len());
+                    }
+                    patch.compare = Some(first);
+                },
+            }
+            let code = match mode {
+                Mode::Nintendo => encode_nes(&patch),
+                _ => gameboy::rom_end(mode).and_then(|rom_end| encode_gameboy(&patch, rom_end)),
+            };
+            code.map(|code| format_code(mode, &code)).ok_or_else(|| Error::Rom(format!("{} can't be written in the other form", format_entry(entry, default))))
+        });
+
+        match converted {
+            Ok(code) => println!("{}\t{code}", format_entry(entry, default)),
+            Err(err) => {
+                error!("{err}");
+                invalid = true;
+            },
+        }
+    }
+
+    exit(if invalid { EXIT_INVALID_CODE } else { EXIT_SUCCESS })
+}
+
+fn encode(address: u32, value: Option<u32>, word: Option<u32>, compare: Option<u32>, mode: Mode) -> ! {
+    if let Mode::Genesis = mode {
+        let Some(word) = word else { Args::fail(ErrorKind::MissingRequiredArgument, "Genesis codes write a 16-bit value, give it with --word".to_string()) };
+        if !fits(mode, word) { Args::fail(ErrorKind::InvalidValue, too_wide(mode, word)) }
+        let value = word as u16;
+        let code = encode_genesis(&Word { address, value })
+            .unwrap_or_else(|| Args::fail(ErrorKind::InvalidValue, format!("Genesis codes write words at even addresses below 0x400000, not {address:#X}")));
+        println!("{}", format_code(mode, &code));
+        exit(EXIT_SUCCESS);
+    }
+
+    let Some(value) = value else { Args::fail(ErrorKind::ArgumentConflict, format!("{mode} codes write a single byte, give it with --value")) };
+    let byte = |name: &str, n: u32| u8::try_from(n).unwrap_or_else(|_| Args::fail(ErrorKind::InvalidValue, too_wide(mode, n).replacen("value", name, 1)));
+    let patch = Patch { address, value: vec![byte("value", value)], compare: compare.map(|compare| byte("compare", compare)) };
+
+    let code = match mode {
+        Mode::GameBoy | Mode::GameGear => {
+            let Some(rom_end) = gameboy::rom_end(mode) else { unreachable!() };
+            encode_gameboy(&patch, rom_end)
+                .unwrap_or_else(|| Args::fail(ErrorKind::InvalidValue, format!("{mode} codes can only address ROM below {rom_end:#X}, not {address:#X}")))
+        },
+        Mode::Nintendo => encode_nes(&patch).unwrap_or_else(|| Args::fail(ErrorKind::InvalidValue, format!("NES addresses are 16-bit, {address:#X} is too large"))),
+        Mode::SuperNintendo => {
+            if patch.compare.is_some() { Args::fail(ErrorKind::ArgumentConflict, "SNES codes have no compare byte".to_string()) }
+            encode_snes(&patch).unwrap_or_else(|| Args::fail(ErrorKind::InvalidValue, format!("SNES addresses are 24-bit, {address:#X} is too large")))
+        },
+        _ => {
+            error!("Encoding {mode} codes isn't supported yet");
+            exit(EXIT_USAGE);
+        }
+    };
+    println!("{}", format_code(mode, &code));
+    exit(EXIT_SUCCESS)
+}
+
+fn selftest() -> ! {
+    let mut failed = 0;
+    for vector in VECTORS {
+        match vector.check() {
+            Ok(()) => println!("ok\t{}\t{}", vector.mode, vector.code),
+            Err(err) => {
+                println!("FAIL\t{}\t{}\t{err}", vector.mode, vector.code);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} of {} passed", VECTORS.len() - failed, VECTORS.len());
+    exit(if failed > 0 { EXIT_INVALID_CODE } else { EXIT_SUCCESS })
+}
+
+fn list_modes() -> ! {
+    for mode in Mode::ALL {
+        println!("{mode}\n  aliases: {}\n  codes:   {}", mode.aliases().join(", "), mode.code_format());
+    }
+    exit(0)
+}
+
+fn is_md(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md"))
+}
+
+fn is_zip_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+// the ROM inside INPUT and its name in the archive, or `None` when INPUT isn't a zip
+#[cfg(feature = "zip")]
+fn unzip(rom_in: &Path, entry: Option<&str>, mode: Mode) -> Result<Option<(String, Vec<u8>)>, Error> {
+    let mut magic = [0_u8; 4];
+    File::open(rom_in).map_err(with_path("read", rom_in))?.read_bytes(&mut magic, 0).map_err(with_path("read", rom_in))?;
+    if !is_zip_path(rom_in) && !zip::is_zip(&magic) { return Ok(None); }
+
+    let archive = fs::read(rom_in).map_err(with_path("read", rom_in))?;
+    let entries = zip::entries(&archive).map_err(with_path("read", rom_in))?;
+    let files: Vec<&zip::Entry> = entries.iter().filter(|entry| !entry.is_dir()).collect();
+    let names = |entries: &[&zip::Entry]| entries.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>().join(", ");
+
+    let chosen: &zip::Entry = match entry {
+        Some(name) => files.iter().find(|entry| entry.name == name || Path::new(&entry.name).file_name().is_some_and(|file| file == name))
+            .copied().ok_or_else(|| Error::Rom(format!("{} has no entry {name} (it holds {})", rom_in.display(), names(&files))))?,
+        None => {
+            let roms: Vec<&zip::Entry> = files.iter().copied()
+                .filter(|entry| Path::new(&entry.name).extension().is_some_and(|ext| mode.extensions().iter().any(|known| ext.eq_ignore_ascii_case(known))))
+                .collect();
+            match (&files[..], &roms[..]) {
+                ([only], _) | (_, [only]) => only,
+                ([], _) => return Err(Error::Rom(format!("{} is an empty archive", rom_in.display()))),
+                (_, []) => return Err(Error::Rom(format!("{} holds no {mode} ROM ({}); pick one with --entry", rom_in.display(), names(&files)))),
+                (_, roms) => return Err(Error::Rom(format!("{} holds several {mode} ROMs ({}); pick one with --entry", rom_in.display(), names(roms)))),
+            }
+        },
+    };
+
+    debug!("Patching {} from {}", chosen.name, rom_in.display());
+    let contents = zip::read(&archive, chosen).map_err(with_path("read", rom_in))?;
+    Ok(Some((chosen.name.clone(), contents)))
+}
+
+// without zip support, an archive is at least recognised rather than patched as garbage
+#[cfg(not(feature = "zip"))]
+fn unzip(rom_in: &Path, _entry: Option<&str>, _mode: Mode) -> Result<Option<(String, Vec<u8>)>, Error> {
+    let mut magic = [0_u8; 2];
+    File::open(rom_in).map_err(with_path("read", rom_in))?.read_bytes(&mut magic, 0).map_err(with_path("read", rom_in))?;
+    if is_zip_path(rom_in) || magic == *b"PK" {
+        return Err(Error::Rom(format!("{} is a zip archive; rebuild with --features zip to patch ROMs inside archives", rom_in.display())));
+    }
+    Ok(None)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// attach the path to an I/O error so the message says which file failed
+fn with_path<'a>(action: &'a str, path: &'a Path) -> impl FnOnce(io::Error) -> Error + 'a {
+    move |err| Error::Io(io::Error::new(err.kind(), format!("Unable to {action} {}: {err}", path.display())))
+}
+
+// fail before INPUT is copied anywhere if an output can't be written: its directory is
+// missing, or the file (or, when it doesn't exist yet, a new file there) can't be opened for writing
+fn check_writable(output: &Path) -> Result<(), Error> {
+    let dir = match output.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Path::new("."),
+        Some(dir) => dir,
+        None => Path::new("/"),
+    };
+    if !dir.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unable to write {}: {} is not a directory", output.display(), dir.display())).into());
+    }
+
+    if output.exists() {
+        // opened without truncating, so the file is left as it was
+        File::options().write(true).open(output).map_err(with_path("write", output))?;
+    } else {
+        File::options().write(true).create_new(true).open(output).map_err(with_path("write", output))?;
+        remove_file(output).map_err(with_path("write", output))?;
+    }
+    Ok(())
+}
+
+// copy() carries INPUT's permissions over, so a read-only INPUT would otherwise
+// give a copy that fails to open for patching after the copy has been made
+fn copy_writable(from: &Path, to: &Path) -> io::Result<()> {
+    copy(from, to)?;
+    let mut permissions = fs::metadata(to)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(to, permissions)
+}
+
+// whether two files hold the same bytes, read a block at a time so neither is loaded whole
+fn same_contents(a: &Path, b: &Path) -> Result<bool, Error> {
+    let (mut a_file, mut b_file) = (File::open(a).map_err(with_path("read", a))?, File::open(b).map_err(with_path("read", b))?);
+    if a_file.metadata()?.len() != b_file.metadata()?.len() { return Ok(false); }
+
+    let (mut a_block, mut b_block) = (vec![0_u8; 1 << 16], vec![0_u8; 1 << 16]);
+    loop {
+        let read = a_file.read(&mut a_block).map_err(with_path("read", a))?;
+        if read == 0 { return Ok(true); }
+        b_file.read_exact(&mut b_block[..read]).map_err(with_path("read", b))?;
+        if a_block[..read] != b_block[..read] { return Ok(false); }
+    }
+}
+
+// build an output next to `path` and move it into place in one step, so a failed
+// run leaves whatever was at `path` before untouched rather than clobbered or deleted
+fn replace_file(path: &Path, fill: impl FnOnce(&Path) -> Result<(), Error>) -> Result<(), Error> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".rggp-tmp");
+    let temp = path.with_file_name(name);
+
+    let replaced = fill(&temp).and_then(|()| fs::rename(&temp, path).map_err(with_path("write", path)));
+    if replaced.is_err() { let _ = remove_file(&temp); }
+    replaced
+}
+
+// the rest of the codes still go in, but a skipped one fails the run
+fn compare_mismatches(results: &[PatchResult]) -> Result<(), Error> {
+    match results.iter().filter(|result| matches!(result.status, ApplyStatus::SkippedCompareMismatch { .. })).count() {
+        0 => Ok(()),
+        n => Err(Error::CompareMismatch(n)),
+    }
+}
+
+fn count(codes: &[&str], locator: &mut Locator, rom: Option<&dyn Image>) -> ! {
+    let (mut valid, mut invalid, mut changed) = (0, 0, 0);
+
+    for code in codes {
+        let Some(rom) = rom else {
+            match locator.decode(code) {
+                Ok(_) => valid += 1,
+                Err(_) => invalid += 1,
+            }
+            continue;
+        };
+
+        let Ok(Located { patch, offsets, .. }) = locator.locate(rom, code) else {
+            invalid += 1;
+            continue;
+        };
+        valid += 1;
+
+        let changes = offsets.iter().any(|&offset| {
+            let mut current = vec![0_u8; patch.value.len()];
+            if let Err(err) = rom.read_bytes(&mut current, offset) {
+                error!("Unable to read ROM file: {err}");
+                exit(EXIT_ROM);
+            }
+            patch.compare.is_none_or(|compare| compare == current[0]) && patch.value != current
+        });
+        if changes { changed += 1; }
+    }
+
+    println!("Valid: {valid}\nInvalid: {invalid}");
+    if rom.is_some() { println!("Changed: {changed}"); }
+    exit(if invalid > 0 { EXIT_INVALID_CODE } else { EXIT_SUCCESS });
+}
+
+// cut an overdump down to `trim` bytes, then zero-fill a short dump up to `pad`
+fn resize_rom(rom: &mut Vec<u8>, pad: Option<u64>, trim: Option<u64>) {
+    let len = rom.len() as u64;
+    let resized = len.min(trim.unwrap_or(len)).max(pad.unwrap_or(0));
+    if resized != len {
+        debug!("Resizing ROM from {len:#X} to {resized:#X} bytes");
+        rom.resize(resized as usize, 0);
+    }
+}
+
+// abort before anything is written if INPUT is already a corrupt dump
+fn verify_checksum(rom_in: &Path, mut rom: Vec<u8>, md: bool, mode: Mode, snes_map: SnesMap) -> Result<(), Error> {
+    if mode == Mode::Genesis && genesis::is_byte_swapped(&rom, md) { genesis::swap_bytes(&mut rom); }
+
+    let sums = checksums(mode, &rom, snes_map).map_err(with_path("read", rom_in))?;
+    if sums.is_empty() {
+        warn!("{mode} ROMs have no checksum to verify");
+        return Ok(());
+    }
+
+    for sum in &sums {
+        debug!("{}: stored {:04X}, computed {:04X}", sum.name, sum.stored, sum.computed);
+    }
+
+    if let Some(sum) = sums.iter().find(|sum| !sum.is_valid()) {
+        return Err(Error::Rom(format!(
+            "{} fails its {} (stored {:04X}, computed {:04X}); refusing to patch a bad dump", rom_in.display(), sum.name, sum.stored, sum.computed,
+        )));
+    }
+    Ok(())
+}
+
+fn fix_checksum(image: &mut [u8], mode: Mode, snes_map: SnesMap) -> Result<(), Error> {
+    let fixed = fix_checksums(mode, image, snes_map)?;
+    if fixed.is_empty() {
+        warn!("{mode} ROMs have no checksum to fix");
+    }
+    for sum in fixed {
+        debug!("{}: was {:04X}, now {:04X}", sum.name, sum.stored, sum.computed);
+    }
+    Ok(())
+}
+
+// a warning rather than an error: overdumps, underdumps and copier headers all still patch
+fn check_size(rom_in: &Path, mut rom: Vec<u8>, md: bool, mode: Mode, snes_map: SnesMap) -> Result<(), Error> {
+    if mode == Mode::Genesis && genesis::is_byte_swapped(&rom, md) { genesis::swap_bytes(&mut rom); }
+
+    let Some(size) = declared_size(mode, &rom, snes_map).map_err(with_path("read", rom_in))? else {
+        warn!("{mode} ROMs have no size field to check");
+        return Ok(());
+    };
+    debug!("{}: declared {:#X}, actual {:#X}", size.name, size.declared, size.actual);
+    if !size.is_valid() {
+        warn!("{} holds {:#X} bytes of ROM but its header declares {:#X}; it may be overdumped, underdumped or have a copier header", rom_in.display(), size.actual, size.declared);
+    }
+    Ok(())
+}
+
+// a warning rather than an error: the magic bytes are only a hint, but a wrong MODE is the usual cause of a garbled ROM
+fn check_mode(rom_in: &Path, rom: &dyn Image, mode: Mode) -> Result<(), Error> {
+    let detected = sniff(rom).map_err(with_path("read", rom_in))?;
+    let matches = |found: Mode| found == mode || matches!((found, mode), (Mode::GameGear, Mode::MasterSystem));
+    let expected = match mode {
+        Mode::Nintendo => Some("the iNES header (NES\\x1A)"),
+        Mode::Genesis => Some("SEGA at 0x100"),
+        Mode::GameBoy => Some("the Nintendo logo at 0x104"),
+        _ => None,
+    };
+    match (detected, expected) {
+        (Some((found, _)), _) if matches(found) => {},
+        (Some((found, evidence)), _) => warn!("{} looks like a {found} ROM ({evidence}), not {mode}; is MODE right? (--force skips this check)", rom_in.display()),
+        (None, Some(expected)) => warn!("{} doesn't have {expected} {mode} ROMs have, so MODE may not match it (--force skips this check)", rom_in.display()),
+        (None, None) => debug!("{} has no system's magic bytes to check MODE against", rom_in.display()),
+    }
+    Ok(())
+}
+
+fn patch_rom(locator: &mut Locator, image: &mut impl Image, entries: &[ListEntry], force: bool, show_progress: bool, explain: bool) -> Result<Vec<PatchResult>, Error> {
+    // guards are checked against the ROM as it was, before any of the codes go in
+    let mut guards: HashMap<&str, Option<u8>> = HashMap::new();
+    let mut blocked = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let mut found = None;
+        for guard in entry.guards.iter().filter(|_| !entry.disabled) {
+            let outcome = match guards.get(guard.as_str()) {
+                Some(&outcome) => outcome,
+                None => {
+                    let outcome = check_guard(locator, &*image, guard)?;
+                    if let Some(byte) = outcome { info!("Guard ?{guard} found {byte:02X} rather than its compare byte; skipping the codes it guards"); }
+                    guards.insert(guard, outcome);
+                    outcome
+                },
+            };
+            found = found.or(outcome);
+        }
+        blocked.push(found);
+    }
+
+    let codes: Vec<&str> = entries.iter().zip(&blocked).filter(|(entry, found)| !entry.disabled && found.is_none()).map(|(entry, _)| entry.code.as_str()).collect();
+    // every code has to decode and map before the first is written, so a bad one late in the
+    // list leaves the ROM as it was, which matters when INPUT is patched in place
+    for code in &codes {
+        let Located { mode, patch, .. } = locator.locate(&*image, code)?;
+        if explain { info!("{}:\n  {}", format_entry(code, mode), locator.explain(mode, patch.address).join("\n  ")); }
+    }
+    let mut progress = Progress::new("codes", if show_progress { codes.len() } else { 0 });
+    let mut per_code = Vec::with_capacity(codes.len());
+    let applied = apply_codes_with(locator, image, &codes, force, |done| {
+        per_code.push(done.len());
+        progress.tick();
+    })?;
+
+    // each code's results come out together, so hand its label to that many in turn,
+    // slotting the disabled and guarded codes' rows in where they sit in the list
+    let mut results = Vec::with_capacity(applied.len());
+    let (mut applied, mut per_code) = (applied.into_iter(), per_code.into_iter());
+    for (entry, found) in entries.iter().zip(blocked) {
+        let done = match (entry.disabled, found) {
+            // a switched off code that no longer decodes is no reason to fail the run
+            (true, _) => disabled_code(locator, image, &entry.code).unwrap_or_else(|err| {
+                debug!("{} is disabled, and doesn't apply anyway: {err}", entry.code);
+                Vec::new()
+            }),
+            (false, Some(found)) => skipped_code(locator, image, &entry.code, ApplyStatus::SkippedGuard { found })?,
+            (false, None) => applied.by_ref().take(per_code.next().unwrap_or(0)).collect(),
         };
-        
-        let mut check_byte = [0_u8];
-        file.read_at(&mut check_byte[..], address as u64 + base_offset).expect("Unable to read ROM file");
-        println!("Address: {address:X}\nValue: {:X}\nCheck: {:X}\nLocation: {:X}", (res_data[4] << 4) + res_data[5], (res_data[6] << 4) + res_data[7], address as u64 + base_offset);
-        return if check_byte[0] != ((res_data[6] << 4) + res_data[7]) { (address as u64 + base_offset, check_byte[0]) } else { (address as u64 + base_offset, (res_data[4] << 4) + res_data[5]) };
-    } else { // invalid state
-        eprintln!("Invalid code length for {code} ({}), expected 6 or 8", code.len());
-        panic!();
+        results.extend(done.into_iter().map(|result| PatchResult { label: entry.label.clone(), ..result }));
     }
+    Ok(results)
 }
 
 fn main() {
-    let args = Args::parse();
-    
-    if !args.rom_in.is_file() {
-        eprintln!("Unable to read {}", args.rom_in.display());
-        return;
+    let given = |flags: &[&str]| env::args_os().skip(1).take_while(|arg| arg != "--").any(|arg| {
+        let arg = arg.to_string_lossy();
+        flags.iter().any(|flag| arg == *flag || arg.starts_with(&format!("{flag}=")))
+    });
+    let set = |var: &str| env::var_os(var).is_some_and(|value| !value.is_empty());
+    // with --code, --codes-file or --db the CODES positional is optional, which would shift MODE into its place, so it becomes --codes instead;
+    // likewise a positional that an RGGP_ variable supplies becomes an option, so the ones after it still line up
+    let mut command = Args::command();
+    if given(&["--code", "--codes-file", "--db", "--codes"]) || set("RGGP_CODES") {
+        command = command.mut_arg("codes", |arg| arg.long("codes"));
+    }
+    if given(&["--mode"]) || set("RGGP_MODE") {
+        command = command.mut_arg("mode", |arg| arg.long("mode"));
+    }
+    if given(&["--input"]) || set("RGGP_INPUT") {
+        command = command.mut_arg("rom_in", |arg| arg.long("input"));
+    }
+    let matches = command.get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if args.list_modes {
+        list_modes();
+    }
+    if let Some(Command::Decode { codes, modes }) = &args.command {
+        decode(codes, modes);
+    }
+    if let Some(Command::Banks { rom_in, mode }) = &args.command {
+        banks(rom_in, *mode);
+    }
+    if let Some(Command::Selftest) = args.command {
+        selftest();
+    }
+    if let Some(Command::Completions { shell }) = args.command {
+        completions::generate(shell, &mut Args::command(), &mut io::stdout().lock()).unwrap_or_else(|err| {
+            error!("{err}");
+            exit(EXIT_ROM);
+        });
+        exit(EXIT_SUCCESS);
+    }
+    if let Some(Command::Table { codes, mode, rom_in, snes_map }) = &args.command {
+        table(codes, *mode, rom_in.as_deref(), *snes_map);
     }
+    if let Some(Command::Catalog { codes_file, mode, csv, strict }) = &args.command {
+        catalog(codes_file, *mode, *csv, *strict);
+    }
+    if let Some(Command::Convert { codes, mode, rom_in, to }) = &args.command {
+        convert(codes, *mode, rom_in.as_deref(), *to);
+    }
+    if let Some(Command::Encode { address, value, word, compare, mode }) = args.command {
+        encode(address, value, word, compare, mode);
+    }
+    args.merge_config(&matches);
 
-    let codes: Vec<&str> = args.codes.split('+').collect();
+    // the positional (or config) list comes first, then each --code in order, then the code file
+    let mut codes: Vec<ListEntry> = args.codes.iter().flat_map(|codes| codes.split('+')).chain(args.code.iter().map(String::as_str)).map(ListEntry::parse).collect();
+    if let Some(path) = &args.codes_file {
+        let src = if path.as_os_str() == "-" { io::read_to_string(io::stdin()) } else { fs::read_to_string(path) };
+        let src = src.unwrap_or_else(|err| Args::fail(ErrorKind::InvalidValue, format!("Unable to read {}: {err}", path.display())));
+        // an FCEUX cheat file is address:value rows rather than codes
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cht")) {
+            codes.extend(parse_cht(&src).unwrap_or_else(|err| Args::fail(ErrorKind::InvalidValue, format!("{}: {err}", path.display()))));
+        } else {
+            codes.extend(parse_code_file(&src));
+        }
+    }
+    // then the database cheats, in --select order
+    if let Some(path) = &args.db {
+        let db = fs::read_to_string(path).map_err(|err| err.to_string()).and_then(|src| parse_db(&src))
+            .unwrap_or_else(|err| Args::fail(ErrorKind::InvalidValue, format!("{}: {err}", path.display())));
+        if args.select.is_empty() {
+            Args::fail(ErrorKind::MissingRequiredArgument, format!("--db needs --select, or select in --config, to pick cheats from {}", path.display()));
+        }
+        for selector in &args.select {
+            let selected = select(&db, selector);
+            if selected.is_empty() {
+                let available: Vec<String> = db.iter().map(DbEntry::key).collect();
+                Args::fail(ErrorKind::InvalidValue, format!("{} has no cheat {selector:?}; it has {}", path.display(), available.join(", ")));
+            }
+            codes.extend(selected.iter().flat_map(|entry| entry.list_entries()));
+        }
+    }
+    let (false, Some(mode)) = (codes.is_empty(), args.mode) else {
+        Args::fail(ErrorKind::MissingRequiredArgument, "CODES and MODE are required, on the command line or in --config".to_string());
+    };
+    if args.rom_in.is_none() && args.buffer.is_none() && !args.count {
+        Args::fail(ErrorKind::MissingRequiredArgument, "INPUT is required, on the command line or in --config".to_string());
+    }
+    if args.rom_out.is_none() && args.outputs.is_empty() && args.out_dir.is_none() && args.buffer.is_none() && !args.count && !args.stdout && !args.dry_run {
+        Args::fail(ErrorKind::MissingRequiredArgument, "OUTPUT is required, on the command line or in --config".to_string());
+    }
+    if let (Some(pad), Some(trim)) = (args.pad, args.trim) {
+        if pad > trim { Args::fail(ErrorKind::ArgumentConflict, format!("--pad {pad:#X} is larger than --trim {trim:#X}")); }
+    }
+    if (args.rom_out.is_some() || !args.outputs.is_empty()) && args.stdout {
+        Args::fail(ErrorKind::ArgumentConflict, "OUTPUT can't be combined with --stdout".to_string());
+    }
+
+    // RUST_LOG picks the level, -v and --quiet override it
+    let level = match (args.verbose, args.quiet) {
+        (true, _) => Level::Debug,
+        (_, true) => Level::Error,
+        _ => env::var("RUST_LOG").ok().and_then(|level| level.parse().ok()).unwrap_or(Level::Info),
+    };
+    log::set_max_level(level);
 
-    let _ = copy(&args.rom_in, &args.rom_out);
+    if args.exec.is_some() && args.rom_in.as_ref().is_some_and(|rom_in| rom_in.is_dir()) {
+        Args::fail(ErrorKind::ArgumentConflict, "--exec can't be used with a directory INPUT".to_string());
+    }
+    // clap catches these on the command line, but not once --config has filled some of them in
+    if args.manifest && (args.stdout || args.count || args.dry_run || args.buffer.is_some()) {
+        Args::fail(ErrorKind::ArgumentConflict, "--manifest records the OUTPUT file it's written next to, so it can't be combined with --stdout, --count, --dry-run or --buffer".to_string());
+    }
+    if args.exec.is_some() && (args.stdout || args.count || args.dry_run || args.buffer.is_some()) {
+        Args::fail(ErrorKind::ArgumentConflict, "--exec needs an OUTPUT file to run on, so it can't be combined with --stdout, --count, --dry-run or --buffer".to_string());
+    }
+    let exec = args.exec.as_deref().map(|command| {
+        split_command(command).unwrap_or_else(|err| Args::fail(ErrorKind::InvalidValue, format!("--exec: {err}")))
+    });
+
+    if let Err(err) = args.apply_out_dir().and_then(|()| run(&args, &codes, mode)).and_then(|()| exec.map_or(Ok(()), |command| launch(&args, command))) {
+        error!("{err}");
+        exit(err.exit_code());
+    }
+}
 
-    let Ok(file) = File::options().write(true).read(true).open(&args.rom_out) else {
-        eprintln!("Unable to open ROM file for reading");
-        if args.rom_out.exists() { remove_file(args.rom_out).unwrap(); }
-        return;
+// split a command line into its words the way a shell would, without running one:
+// whitespace separates words, quotes group them and a backslash escapes the next character
+fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let (mut words, mut word, mut quote, mut in_word) = (Vec::new(), String::new(), None, false);
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.push(c),
+            (_, '\\') => {
+                word.push(chars.next().ok_or("it ends in a backslash")?);
+                in_word = true;
+            },
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            },
+            (None, c) if c.is_whitespace() => {
+                if in_word { words.push(std::mem::take(&mut word)); }
+                in_word = false;
+            },
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+    if quote.is_some() { return Err("it has an unclosed quote".to_string()); }
+    if in_word { words.push(word); }
+    if words.is_empty() { return Err("no command given".to_string()); }
+    Ok(words)
+}
+
+// run the --exec command on the patched OUTPUT, handing the path over as its own argument
+fn launch(args: &Args, mut command: Vec<String>) -> Result<(), Error> {
+    let Some(output) = args.rom_out.as_ref().or(args.outputs.first()) else {
+        return Err(Error::Usage("--exec needs an OUTPUT file to run on".to_string()));
     };
+    let path = output.to_string_lossy();
+    match command.iter().any(|word| word.contains("{}")) {
+        true => for word in &mut command { *word = word.replace("{}", &path); },
+        false => command.push(path.into_owned()),
+    }
+
+    debug!("Running {command:?}");
+    let status = process::Command::new(&command[0]).args(&command[1..]).status()
+        .map_err(|err| io::Error::new(err.kind(), format!("Unable to run {}: {err}", command[0])))?;
+    if !status.success() {
+        warn!("{} exited with {status}", command[0]);
+    }
+    Ok(())
+}
+
+fn run(args: &Args, codes: &[ListEntry], mode: Mode) -> Result<(), Error> {
+    if codes.len() > args.max_codes {
+        Args::command().error(
+            ErrorKind::TooManyValues,
+            format!("more than {} codes given; raise --max-codes to apply more", args.max_codes),
+        ).exit();
+    }
 
-    use Mode::*;
-    match args.mode {
-        #[allow(unused_parens)]
-        ( GameBoy | GB ) | ( GameGear | GG ) | ( MasterSystem | SMS ) => { todo!("Unemplimented") },
+    if let Some(rom_in) = &args.rom_in {
+        if rom_in.is_dir() {
+            return batch(args, rom_in, codes, mode);
+        }
+        if !rom_in.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unable to read {}: not a file", rom_in.display())).into());
+        }
+    }
+
+    // found unwritable now rather than after the ROM is patched
+    if let Some(path) = &args.summary_file { check_writable(path)?; }
 
-        #[allow(unused_parens)]
-        ( Genesis | SG ) | ( MegaDrive | MD ) => { todo!("Unemplimented") },
-        
-        Nintendo | NES => {
-            let mut global_offset = 0x10_u64;
+    let results = match &args.buffer {
+        Some(buffer) => patch_buffer(args, codes, mode, buffer)?,
+        None => patch_one(args, codes, mode, true)?,
+    };
+    if args.manifest { write_manifest(args, codes, mode, &results)?; }
+    // a dry run shows its changes even without a summary format, which picks a machine readable one instead
+    let format = args.summary_format();
+    if format.is_some() || args.dry_run {
+        let mut report = Vec::new();
+        match (format, args.by_system) {
+            (None, _) => write_diff(&mut report, &results)?,
+            (Some(format), true) => write_by_system(&mut report, format, &results)?,
+            (Some(format), false) => write_summary(&mut report, format, &results)?,
+        }
+        match &args.summary_file {
+            Some(path) => fs::write(path, report).map_err(with_path("write", path))?,
+            None => io::stdout().lock().write_all(&report)?,
+        }
+    }
+    // a dry run writes nothing to tally
+    if !args.dry_run { info!("{}", tally(&results)); }
+    compare_mismatches(&results)
+}
 
-            for code in codes {
-                let (offset, data) = parse_nes(code, &file, global_offset);
-                global_offset += 0x8000;
-                file.write_at(&[data], offset as u64).expect("Unable to write code data to file");
+// patch every MODE ROM in the INPUT directory into the OUTPUT directory, a few files at a time
+fn batch(args: &Args, dir: &Path, codes: &[ListEntry], mode: Mode) -> Result<(), Error> {
+    if args.count || args.stdout || args.dry_run || args.manifest || !args.outputs.is_empty() || args.summary_format().is_some() {
+        Args::fail(ErrorKind::ArgumentConflict, "a directory INPUT takes a single OUTPUT directory, without --count, --stdout, --dry-run, --manifest, -o or a summary".to_string());
+    }
+    let Some(out_dir) = &args.rom_out else { unreachable!() };
+    if same_file(dir, out_dir) {
+        Args::fail(ErrorKind::ArgumentConflict, "OUTPUT has to be a different directory from INPUT".to_string());
+    }
+    fs::create_dir_all(out_dir).map_err(with_path("create", out_dir))?;
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(with_path("read", dir))? {
+        let path = entry.map_err(with_path("read", dir))?.path();
+        let rom = path.extension().is_some_and(|ext| mode.extensions().iter().chain(&["zip"]).any(|known| ext.eq_ignore_ascii_case(known)));
+        if rom && path.is_file() { files.push(path); }
+    }
+    // sorted so the report reads the same whatever order the threads finish in
+    files.sort();
+    if files.is_empty() {
+        return Err(Error::Rom(format!("{} holds no {mode} ROMs ({})", dir.display(), mode.extensions().join(", "))));
+    }
+
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(files.len());
+    let mut outcomes: Vec<(usize, Result<Vec<PatchResult>, Error>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers).map(|_| scope.spawn(|| {
+            let mut done = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = files.get(index) else { return done };
+                let job = Args { rom_in: Some(file.clone()), rom_out: file.file_name().map(|name| out_dir.join(name)), ..args.clone() };
+                // --strict judges each file by its own warnings, not the ones this worker logged for the last
+                log::reset_thread_warnings();
+                done.push((index, patch_one(&job, codes, mode, false).and_then(|results| compare_mismatches(&results).map(|()| results))));
             }
-        },
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    });
+    outcomes.sort_by_key(|(index, _)| *index);
+
+    let mut failures = Vec::new();
+    for (index, outcome) in outcomes {
+        let name = files[index].file_name().unwrap_or_default().to_string_lossy();
+        match outcome {
+            Ok(results) => {
+                let applied = results.iter().filter(|result| matches!(result.status, ApplyStatus::Applied | ApplyStatus::Forced { .. })).count();
+                println!("{name}\tok\t{applied} applied, {} unchanged", results.len() - applied);
+            },
+            Err(err) => {
+                println!("{name}\tfailed\t{err}");
+                failures.push(err);
+            },
+        }
+    }
 
-        SuperNintendo | SNES => { todo!("Unemplimented") }
+    let failed = failures.len();
+    match failures.into_iter().next() {
+        None => Ok(()),
+        Some(first) => Err(Error::Batch { failed, total: files.len(), first: Box::new(first) }),
+    }
+}
+
+// a --config file that patches INPUT the same way again, with each patch it made listed in comments
+fn write_manifest(args: &Args, codes: &[ListEntry], mode: Mode, results: &[PatchResult]) -> Result<(), Error> {
+    let (Some(rom_in), Some(output)) = (&args.rom_in, args.rom_out.as_ref().or(args.outputs.first())) else {
+        return Err(Error::Usage("--manifest needs INPUT and an OUTPUT file".to_string()));
     };
+    #[cfg(feature = "zip")]
+    let (entry, zip_output) = (args.entry.as_deref(), args.zip_output);
+    #[cfg(not(feature = "zip"))]
+    let (entry, zip_output) = (None, false);
+    let crc = crc32(&match unzip(rom_in, entry, mode)? {
+        Some((_, contents)) => contents,
+        None => fs::read(rom_in).map_err(with_path("read", rom_in))?,
+    });
+
+    let mut manifest = Vec::new();
+    writeln!(manifest, "# How {} was made; `rggp --config {}.manifest.toml -o OUTPUT` makes it again", output.display(), output.display())?;
+    let input = rom_in.canonicalize().map_err(with_path("read", rom_in))?;
+    writeln!(manifest, "input = {}", config::quote(&input.to_string_lossy()))?;
+    writeln!(manifest, "expect-crc = \"{crc:08X}\"")?;
+    writeln!(manifest, "mode = {}", config::quote(mode.aliases()[0]))?;
+    writeln!(manifest, "codes = [")?;
+    for entry in codes.iter().filter(|entry| !entry.disabled) {
+        writeln!(manifest, "    {},", config::quote(&entry.format(mode)))?;
+    }
+    writeln!(manifest, "]")?;
+    // only what changes where codes land or what's written
+    if args.snes_map != SnesMap::Auto {
+        let map = args.snes_map.to_possible_value().map_or(String::new(), |value| value.get_name().to_string());
+        writeln!(manifest, "snes-map = {}", config::quote(&map))?;
+    }
+    if let Some(bank) = args.bank { writeln!(manifest, "bank = {bank}")?; }
+    if args.all_banks { writeln!(manifest, "all-banks = true")?; }
+    if args.first_match { writeln!(manifest, "first-match = true")?; }
+    if let Some(pad) = args.pad { writeln!(manifest, "pad = {pad}")?; }
+    if let Some(trim) = args.trim { writeln!(manifest, "trim = {trim}")?; }
+    if args.fix_checksum { writeln!(manifest, "fix-checksum = true")?; }
+    if let Some((start, end)) = args.extract_region { writeln!(manifest, "extract-region = \"{start:#X}:{end:#X}\"")?; }
+    if args.force { writeln!(manifest, "force = true")?; }
+    if let Some(entry) = entry { writeln!(manifest, "entry = {}", config::quote(entry))?; }
+    if zip_output { writeln!(manifest, "zip-output = true")?; }
+
+    writeln!(manifest, "\n# code, system, address @ file offset, old -> new, status")?;
+    let mut patches = Vec::new();
+    write_summary(&mut patches, SummaryFormat::Text, results)?;
+    for line in String::from_utf8_lossy(&patches).lines() {
+        writeln!(manifest, "# {line}")?;
+    }
+
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".manifest.toml");
+    let path = output.with_file_name(name);
+    fs::write(&path, manifest).map_err(with_path("write", &path))?;
+    debug!("Wrote {}", path.display());
+    Ok(())
+}
+
+// patch the --buffer bytes in memory, laid out as they would be in a file, and print them back as hex
+fn patch_buffer(args: &Args, codes: &[ListEntry], mode: Mode, buffer: &str) -> Result<Vec<PatchResult>, Error> {
+    let buffer = if buffer == "-" { io::read_to_string(io::stdin())? } else { buffer.to_string() };
+    let digits: Vec<u8> = buffer.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    let mut image = digits.chunks(2).map(|pair| match pair {
+        [high, low] => std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            .ok_or_else(|| format!("{}{} isn't a hex byte", *high as char, *low as char)),
+        _ => Err("it has an odd number of hex digits".to_string()),
+    }).collect::<Result<Vec<u8>, String>>().unwrap_or_else(|err| Args::fail(ErrorKind::InvalidValue, format!("--buffer: {err}")));
+
+    let mut locator = Locator::new(mode, args.snes_map, args.bank).with_all_banks(args.all_banks);
+    if args.count {
+        count(&codes.iter().filter(|entry| !entry.disabled).map(|entry| entry.code.as_str()).collect::<Vec<_>>(), &mut locator, Some(&image));
+    }
+    let results = patch_rom(&mut locator, &mut image, codes, args.force, false, args.explain_offsets)?;
+    if args.fix_checksum { fix_checksum(&mut image, mode, args.snes_map)?; }
+    if args.strict && log::thread_warnings() > 0 {
+        return Err(Error::Warnings(log::thread_warnings()));
+    }
+    println!("{}", hex(&image));
+    Ok(results)
+}
+
+// patch one ROM as the arguments describe, returning a result per code
+fn patch_one(args: &Args, codes: &[ListEntry], mode: Mode, show_progress: bool) -> Result<Vec<PatchResult>, Error> {
+    #[cfg(feature = "zip")]
+    let (entry, zip_output) = (args.entry.as_deref(), args.zip_output);
+    #[cfg(not(feature = "zip"))]
+    let (entry, zip_output) = (None, false);
+
+    // a zipped ROM is unpacked into memory and patched there, and so is one being padded or trimmed
+    let zipped = match &args.rom_in {
+        Some(rom_in) => unzip(rom_in, entry, mode)?,
+        None => None,
+    };
+    let loaded = match (&args.rom_in, &zipped) {
+        (_, Some((_, contents))) => Some(contents.clone()),
+        (Some(rom_in), None) if args.pad.is_some() || args.trim.is_some() => Some(fs::read(rom_in).map_err(with_path("read", rom_in))?),
+        _ => None,
+    }.map(|mut bytes| {
+        resize_rom(&mut bytes, args.pad, args.trim);
+        bytes
+    });
+    let rom: Option<Box<dyn Image>> = match (&args.rom_in, &loaded) {
+        (_, Some(bytes)) => Some(Box::new(bytes.clone())),
+        (Some(rom_in), None) => Some(Box::new(File::open(rom_in).map_err(with_path("read", rom_in))?)),
+        (None, None) => None,
+    };
+    if let (false, Some(rom), Some(rom_in)) = (args.force, &rom, &args.rom_in) {
+        check_mode(rom_in, rom.as_ref(), mode)?;
+    }
+    if let (Mode::Nintendo, Some(rom), Some(rom_in)) = (mode, &rom, &args.rom_in) {
+        let layout = nes::Layout::detect(rom.as_ref()).map_err(with_path("read", rom_in))?;
+        let rom_len = rom.size().unwrap_or(0);
+        if rom_len < layout.prg_start {
+            return Err(Error::Rom(format!("{} is too small to be a valid NES ROM ({rom_len} bytes, expected at least {})", rom_in.display(), layout.prg_start)));
+        }
+        // a bad dump still patches, but its codes may land in the wrong place
+        if let Some(problem) = layout.size_problem(rom_len) {
+            warn!("{}: {problem}", rom_in.display());
+        }
+    }
+
+    let mut locator = Locator::new(mode, args.snes_map, args.bank).with_all_banks(args.all_banks);
+    if args.count {
+        count(&codes.iter().filter(|entry| !entry.disabled).map(|entry| entry.code.as_str()).collect::<Vec<_>>(), &mut locator, rom.as_deref());
+    }
+
+    let Some(rom_in) = args.rom_in.clone() else { unreachable!() };
+    // the name the ROM goes by, which for an archive is its entry's
+    let rom_name = zipped.as_ref().map_or(rom_in.clone(), |(name, _)| PathBuf::from(name));
+    let read_rom = || match &loaded {
+        Some(bytes) => Ok(bytes.clone()),
+        None => fs::read(&rom_in).map_err(with_path("read", &rom_in)),
+    };
+
+    // the CRC of the dump itself, before any --pad or --trim
+    if let Some(expected) = args.expect_crc {
+        let crc = crc32(&match &zipped {
+            Some((_, contents)) => contents.clone(),
+            None => fs::read(&rom_in).map_err(with_path("read", &rom_in))?,
+        });
+        debug!("{}: CRC32 {crc:08X}", rom_name.display());
+        if crc != expected {
+            return Err(Error::Rom(format!("{} has CRC32 {crc:08X}, not {expected:08X}; it's a different dump or revision than the codes are for", rom_in.display())));
+        }
+    }
+    if args.verify_checksum {
+        verify_checksum(&rom_in, read_rom()?, is_md(&rom_name), mode, args.snes_map)?;
+    }
+    if args.check_size {
+        check_size(&rom_in, read_rom()?, is_md(&rom_name), mode, args.snes_map)?;
+    }
+
+    // the first output is patched, the rest are copies of it; clap requires at least one unless --stdout
+    // or --dry-run, which writes none of them
+    let outputs: Vec<PathBuf> = match args.dry_run {
+        true => Vec::new(),
+        false => args.rom_out.clone().into_iter().chain(args.outputs.clone()).collect(),
+    };
+    for output in &outputs {
+        check_writable(output)?;
+    }
+
+    // .md Genesis dumps are patched as a big endian image in memory, then written back in each file's order
+    let swapped = mode == Mode::Genesis && {
+        let mut header = [0_u8; 0x104];
+        rom.as_ref().map_or(Ok(()), |rom| rom.read_bytes(&mut header, 0)).map_err(with_path("read", &rom_in))?;
+        genesis::is_byte_swapped(&header, is_md(&rom_name))
+    };
+    // a zipped output keeps the ROM's own name inside the archive
+    let md_output = mode == Mode::Genesis && if zip_output { is_md(&rom_name) } else { outputs.iter().any(|output| is_md(output)) };
+
+    // --strict patches in memory too, so a warning stops the run before any output is touched,
+    // and so does --fix-checksum, which sums the whole patched ROM; --dry-run only ever patches in memory,
+    // and --extract-region cuts its piece out of the patched image
+    if args.stdout || swapped || md_output || loaded.is_some() || zip_output || args.strict || args.fix_checksum || args.dry_run || args.extract_region.is_some() {
+        // also keeps anything but the ROM itself off stdout
+        let mut image = read_rom()?;
+        if swapped { genesis::swap_bytes(&mut image); }
+        let results = patch_rom(&mut locator, &mut image, codes, args.force, show_progress, args.explain_offsets)?;
+        if args.fix_checksum { fix_checksum(&mut image, mode, args.snes_map)?; }
+        if args.strict && log::thread_warnings() > 0 {
+            return Err(Error::Warnings(log::thread_warnings()));
+        }
+
+        if args.dry_run { return Ok(results); }
+        let region = match args.extract_region {
+            Some((start, end)) if end > image.len() as u64 => {
+                return Err(Error::Rom(format!("--extract-region {start:#X}:{end:#X} runs past the end of the {:#X} byte ROM", image.len())));
+            },
+            Some((start, end)) => start as usize..end as usize,
+            None => 0..image.len(),
+        };
+        if args.stdout {
+            if swapped { genesis::swap_bytes(&mut image); }
+            io::stdout().lock().write_all(&image[region])?;
+            return Ok(results);
+        }
+        for output in outputs {
+            let mut bytes = image.clone();
+            let name = if zip_output { &rom_name } else { &output };
+            if mode == Mode::Genesis && is_md(name) { genesis::swap_bytes(&mut bytes); }
+            bytes.truncate(region.end);
+            bytes.drain(..region.start);
+            #[cfg(feature = "zip")]
+            if zip_output {
+                let entry = rom_name.file_name().map_or("rom".into(), |name| name.to_string_lossy());
+                bytes = zip::write(&entry, &bytes);
+            }
+            replace_file(&output, |temp| fs::write(temp, &bytes).map_err(with_path("write", &output)))?;
+        }
+        return Ok(results);
+    }
+
+    let mut outputs = outputs.into_iter();
+    let Some(rom_out) = outputs.next() else { unreachable!() };
+
+    // copying a file onto itself truncates it, so patch in place instead; with --no-copy, an
+    // OUTPUT that's already byte for byte INPUT is patched in place too, skipping the copy
+    let in_place = same_file(&rom_in, &rom_out) || args.no_copy && rom_out.is_file() && {
+        let unchanged = same_contents(&rom_in, &rom_out)?;
+        if !unchanged { debug!("{} differs from {}, copying it over", rom_out.display(), rom_in.display()); }
+        unchanged
+    };
+    let mut patch_file = |path: &Path| -> Result<Vec<PatchResult>, Error> {
+        let mut file = File::options().write(true).read(true).open(path).map_err(with_path("open", &rom_out))?;
+        patch_rom(&mut locator, &mut file, codes, args.force, show_progress, args.explain_offsets)
+    };
+
+    let results = if in_place {
+        patch_file(&rom_out)?
+    } else {
+        let mut results = Vec::new();
+        replace_file(&rom_out, |temp| {
+            copy_writable(&rom_in, temp).map_err(with_path("write", &rom_out))?;
+            results = patch_file(temp)?;
+            Ok(())
+        })?;
+        results
+    };
+    for copy_out in outputs {
+        if same_file(&rom_out, &copy_out) { continue; }
+        replace_file(&copy_out, |temp| copy_writable(&rom_out, temp).map_err(with_path("write", &copy_out)))?;
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a file holding `bytes` in a scratch directory of its own
+    fn scratch(name: &str, bytes: &[u8]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rggp-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn parse(argv: &[&str]) -> Args {
+        Args::try_parse_from(["rggp"].iter().chain(argv)).unwrap()
+    }
+
+    fn patch(argv: &[&str]) -> Result<Vec<PatchResult>, Error> {
+        let args = parse(argv);
+        let codes: Vec<ListEntry> = args.codes.iter().flat_map(|codes| codes.split('+')).map(ListEntry::parse).collect();
+        patch_one(&args, &codes, args.mode.unwrap(), false)
+    }
+
+    #[test]
+    fn completions_cover_every_long_flag() {
+        let mut cmd = Args::command();
+        cmd.build();
+        let longs: Vec<String> = cmd.get_subcommands().chain([&cmd]).flat_map(|sub| sub.get_arguments()).filter_map(|arg| arg.get_long()).map(|long| format!("--{long}")).collect();
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            completions::generate(*shell, &mut Args::command(), &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+            // fish names the flag without its dashes
+            let listed = |long: &String| match shell {
+                Shell::Fish => script.contains(&format!("-l {} ", &long[2..])),
+                _ => script.contains(long.as_str()),
+            };
+            assert!(longs.iter().all(listed), "{shell:?} is missing {:?}", longs.iter().filter(|long| !listed(long)).collect::<Vec<_>>());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_read_only_input_gives_a_writable_copy() {
+        use std::os::unix::fs::PermissionsExt;
+        let mut rom = vec![0xEA_u8; 0x8010];
+        rom[..8].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 2, 0, 0, 0]);
+        let rom = scratch("read-only.nes", &rom);
+        fs::set_permissions(&rom, fs::Permissions::from_mode(0o444)).unwrap();
+        let out = rom.with_extension("out");
+        let _ = fs::remove_file(&out);
+
+        copy_writable(&rom, &out).unwrap();
+        assert_eq!(fs::metadata(&out).unwrap().permissions().mode() & 0o777, 0o644);
+        assert_eq!(fs::metadata(&rom).unwrap().permissions().mode() & 0o777, 0o444);
+        // and a run patches it, copying over the first copy
+        assert_eq!(patch(&["SXIOPO", "nes", rom.to_str().unwrap(), out.to_str().unwrap()]).unwrap()[0].status, ApplyStatus::Applied);
+        assert_eq!(fs::read(&out).unwrap()[0x11E9], 0xAD);
+    }
+
+    #[test]
+    fn rejects_a_nes_rom_shorter_than_its_header() {
+        let rom = scratch("four-bytes.nes", b"NES\x1A");
+        let out = rom.with_extension("out");
+        let err = patch(&["SXIOPO", "nes", rom.to_str().unwrap(), out.to_str().unwrap()]).unwrap_err();
+        assert!(matches!(&err, Error::Rom(message) if message.contains("too small")), "{err}");
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn rejects_a_nes_rom_shorter_than_its_trainer() {
+        let mut rom = vec![0_u8; 0x100];
+        rom[..8].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 2, 0, 0b0100, 0]);
+        let rom = scratch("trainer.nes", &rom);
+        let out = rom.with_extension("out");
+        let err = patch(&["SXIOPO", "nes", rom.to_str().unwrap(), out.to_str().unwrap()]).unwrap_err();
+        assert!(matches!(&err, Error::Rom(message) if message.contains("expected at least 528")), "{err}");
+    }
+
+    #[test]
+    fn applies_guarded_codes_only_when_the_guard_matches() {
+        let mut rom = vec![0xEA_u8; 0x8010];
+        rom[..8].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 2, 0, 0, 0]);
+        let rom = scratch("guarded.nes", &rom);
+        let out = rom.with_extension("out");
+        let statuses = |codes: &str| -> Vec<ApplyStatus> {
+            patch(&[codes, "nes", rom.to_str().unwrap(), out.to_str().unwrap()]).unwrap().iter().map(|result| result.status).collect()
+        };
+        // ATAPAA writes 60 at $9000; SXSOPOXV compares $91D9 against EA, which the ROM holds, and SXSOPOZS against 5A
+        assert_eq!(statuses("ATAPAA?SXSOPOXV"), [ApplyStatus::Applied]);
+        assert_eq!(statuses("ATAPAA?SXSOPOZS+SXIOPO"), [ApplyStatus::SkippedGuard { found: 0xEA }, ApplyStatus::Applied]);
+        assert_eq!(fs::read(&out).unwrap()[0x1010], 0xEA);
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("0x8000"), Ok(0x8000));
+        assert_eq!(parse_size("512K"), Ok(512 << 10));
+        assert_eq!(parse_size("4m"), Ok(4 << 20));
+        assert_eq!(parse_size("17592186044416M"), Err("17592186044416M is too large".to_string()));
+        assert!(parse_size("K").is_err());
+    }
 }
@@ -0,0 +1,147 @@
+use std::{ffi::OsStr, fmt, str::FromStr};
+
+use clap::{builder::{PossibleValue, TypedValueParser}, error::ErrorKind, Arg, Command};
+
+use crate::{console::Console, gameboy::GameBoy, genesis::Genesis, nes::Nes, snes::Snes};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    GameBoy,
+    GameGear,
+    MasterSystem,
+    Genesis,
+    Nintendo,
+    SuperNintendo,
+}
+
+// spellings accepted for each mode once case, spaces, hyphens and underscores are dropped
+const ALIASES: [(Mode, &[&str]); 6] = [
+    (Mode::GameBoy, &["gameboy", "gb", "gbc", "gameboycolor"]),
+    (Mode::GameGear, &["gamegear", "gg", "segagamegear"]),
+    (Mode::MasterSystem, &["mastersystem", "sms", "segamastersystem"]),
+    (Mode::Genesis, &["genesis", "sg", "sega", "segagenesis", "megadrive", "md", "segamegadrive"]),
+    (Mode::Nintendo, &["nintendo", "nes", "famicom", "fc"]),
+    (Mode::SuperNintendo, &["supernintendo", "snes", "superfamicom", "sfc"]),
+];
+
+impl Mode {
+    pub const ALL: [Mode; 6] = [Mode::GameBoy, Mode::GameGear, Mode::MasterSystem, Mode::Genesis, Mode::Nintendo, Mode::SuperNintendo];
+
+    /// The code format for this mode, if it's implemented yet
+    pub fn console(self) -> Option<&'static dyn Console> {
+        match self {
+            Mode::Nintendo => Some(&Nes),
+            Mode::SuperNintendo => Some(&Snes),
+            Mode::GameBoy | Mode::GameGear => Some(&GameBoy),
+            Mode::Genesis => Some(&Genesis),
+            Mode::MasterSystem => None,
+        }
+    }
+
+    /// The characters this mode's codes are written with, in value order, if it's implemented yet
+    pub fn alphabet(self) -> Option<&'static [char]> {
+        self.console().map(|console| console.alphabet())
+    }
+
+    /// How this mode's codes are written: length, alphabet and whether they're checked
+    pub fn code_format(self) -> String {
+        self.console().map_or("not supported yet".to_string(), |console| console.format())
+    }
+
+    /// File extensions dumps for this mode usually have, lowercase and without the dot
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Mode::Nintendo => &["nes"],
+            Mode::SuperNintendo => &["sfc", "smc", "swc", "fig"],
+            Mode::GameBoy => &["gb", "gbc", "sgb"],
+            Mode::GameGear => &["gg"],
+            Mode::MasterSystem => &["sms"],
+            Mode::Genesis => &["bin", "md", "gen", "smd"],
+        }
+    }
+
+    /// Every spelling `from_str` accepts for this mode, canonical name first
+    pub fn aliases(self) -> &'static [&'static str] {
+        ALIASES.iter().find(|(mode, _)| *mode == self).map(|(_, aliases)| *aliases).unwrap_or(&[])
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Mode::GameBoy => "Game Boy",
+            Mode::GameGear => "Game Gear",
+            Mode::MasterSystem => "Master System",
+            Mode::Genesis => "Genesis",
+            Mode::Nintendo => "NES",
+            Mode::SuperNintendo => "SNES",
+        })
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Mode, String> {
+        let key: String = s.chars().filter(|c| !matches!(c, ' ' | '-' | '_')).flat_map(char::to_lowercase).collect();
+        ALIASES.iter()
+            .find(|(_, aliases)| aliases.contains(&key.as_str()))
+            .map(|(mode, _)| *mode)
+            .ok_or_else(|| format!("unknown mode '{s}'"))
+    }
+}
+
+/// clap value parser for `Mode` that goes through `FromStr`, but still lists the canonical names in help
+#[derive(Debug, Clone, Copy)]
+pub struct ModeParser;
+
+impl TypedValueParser for ModeParser {
+    type Value = Mode;
+
+    fn parse_ref(&self, cmd: &Command, _arg: Option<&Arg>, value: &OsStr) -> Result<Mode, clap::Error> {
+        let value = value.to_string_lossy();
+        value.parse().map_err(|err| {
+            let names: Vec<&str> = Mode::ALL.iter().map(|mode| mode.aliases()[0]).collect();
+            clap::Error::raw(ErrorKind::InvalidValue, format!("{err} (expected one of {})\n", names.join(", "))).with_cmd(cmd)
+        })
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(Mode::ALL.iter().map(|mode| {
+            let aliases = mode.aliases();
+            PossibleValue::new(aliases[0]).aliases(&aliases[1..])
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_common_spellings() {
+        for (spelling, mode) in [
+            ("gameboy", Mode::GameBoy), ("GB", Mode::GameBoy), ("Game Boy", Mode::GameBoy), ("game-boy-color", Mode::GameBoy),
+            ("snes", Mode::SuperNintendo), ("Super Famicom", Mode::SuperNintendo),
+            ("md", Mode::Genesis), ("SEGA", Mode::Genesis), ("Mega Drive", Mode::Genesis), ("mega_drive", Mode::Genesis),
+            ("NES", Mode::Nintendo), ("famicom", Mode::Nintendo), ("gg", Mode::GameGear),
+        ] {
+            assert_eq!(spelling.parse::<Mode>(), Ok(mode), "{spelling}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert_eq!("atari".parse::<Mode>(), Err("unknown mode 'atari'".to_string()));
+        assert!("".parse::<Mode>().is_err());
+    }
+
+    #[test]
+    fn every_alias_parses_back_to_its_mode() {
+        for mode in Mode::ALL {
+            for alias in mode.aliases() {
+                assert_eq!(alias.parse::<Mode>(), Ok(mode), "{alias}");
+            }
+        }
+    }
+}
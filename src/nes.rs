@@ -0,0 +1,382 @@
+use std::io;
+
+use crate::{codes::normalize, console::Console, debug, error::CodeError, patch::Patch, rom::{Image, Mapping}};
+
+pub const NES_CONVERSION: [char; 16] = ['A', 'P', 'Z', 'L', 'G', 'I', 'T', 'Y', 'E', 'O', 'X', 'U', 'K', 'S', 'V', 'N'];
+
+/// 6 letters, or 8 with a compare byte
+pub const NES_LENGTHS: &[usize] = &[6, 8];
+
+/// Where PRG ROM sits in a NES file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// File offset of the first PRG byte, past the header and any trainer
+    pub prg_start: u64,
+    /// PRG size from the header, or `None` when the file has no iNES header
+    pub prg_size: Option<u64>,
+    /// CHR size from the header, which follows PRG in the file
+    pub chr_size: Option<u64>,
+    /// 16KB PRG bank to resolve every address into, instead of the default
+    /// mapping of `$8000-$FFFF` onto the start of PRG
+    pub bank: Option<u64>,
+    /// File length; without a header, PRG is taken to be the rest of the file
+    pub size: u64,
+}
+
+impl Layout {
+    /// Layout for a 16 byte header with no iNES magic or PRG size to go on,
+    /// in a file of unknown length
+    pub const HEADERLESS: Layout = Layout { prg_start: 0x10, prg_size: None, chr_size: None, bank: None, size: u64::MAX };
+
+    pub fn detect<I: Image + ?Sized>(image: &I) -> io::Result<Layout> {
+        let mut header = [0_u8; 16];
+        image.read_bytes(&mut header, 0)?;
+        if is_fds(&header) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "it's a Famicom Disk System image, and FDS games run from RAM loaded off disk, so codes don't map to a place in the file",
+            ));
+        }
+        let size = image.size()?;
+        if !header.starts_with(b"NES\x1A") { return Ok(Layout { size, ..Layout::HEADERLESS }); }
+
+        // iNES header, followed by a 512 byte trainer when bit 2 of flags 6 is set
+        let prg_start = if header[6] & 0b0100 != 0 { 0x210 } else { 0x10 };
+        // NES 2.0 keeps the upper bits of the PRG bank count in byte 9
+        let nes2 = header[7] & 0x0C == 0x08;
+        let prg_size = rom_size(header[4], if nes2 { header[9] & 0x0F } else { 0 }, 0x4000);
+        let chr_size = rom_size(header[5], if nes2 { header[9] >> 4 } else { 0 }, 0x2000);
+        debug!("iNES header: {prg_size:#X} bytes of PRG at {prg_start:#X}, {chr_size:#X} of CHR{}", if nes2 { " (NES 2.0)" } else { "" });
+
+        Ok(Layout { prg_start, prg_size: Some(prg_size), chr_size: Some(chr_size), bank: None, size })
+    }
+
+    /// What's wrong with the PRG and CHR sizes the header declares, given a
+    /// `file_len` byte file: PRG that isn't whole 16KB banks, which breaks
+    /// mirroring and bank math, or a file shorter or longer than the header
+    /// and the two of them. `None` when they fit, or there's no iNES header
+    pub fn size_problem(&self, file_len: u64) -> Option<String> {
+        let (Some(prg), Some(chr)) = (self.prg_size, self.chr_size) else { return None };
+        let declared = self.prg_start + prg + chr;
+        if prg % 0x4000 != 0 {
+            Some(format!("its header declares {prg:#X} bytes of PRG, which isn't a whole number of 16KB banks"))
+        } else if file_len != declared {
+            Some(format!(
+                "its header declares {prg:#X} bytes of PRG and {chr:#X} of CHR after {:#X} of header, {declared:#X} in all, but the file is {file_len:#X} bytes{}",
+                self.prg_start, if file_len < declared { " (truncated?)" } else { "" },
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Every file offset CPU `address` can reach. Past 32KB of PRG, which
+    /// 16KB bank sits at `$8000-$FFFF` depends on the mapper, so without a
+    /// `bank` that's the same spot in each bank, as a Game Genie on the real
+    /// console would patch whichever one is switched in
+    pub fn file_offsets(&self, address: u32) -> Vec<u64> {
+        match self.prg_size {
+            Some(size) if self.bank.is_none() && size > 0x8000 => {
+                (0..size / 0x4000).map(|bank| self.prg_start + bank * 0x4000 + (address as u64 & 0x3FFF)).filter(|&offset| offset < self.size).collect()
+            },
+            _ => self.file_offset(address).into_iter().collect(),
+        }
+    }
+
+    /// File offset of CPU `address` (`$8000` based, as decoded), or `None`
+    /// when it falls outside PRG ROM or past the end of the file
+    pub fn file_offset(&self, address: u32) -> Option<u64> {
+        self.prg_offset(address as u64).map(|prg| self.prg_start + prg).filter(|&offset| offset < self.size)
+    }
+
+    // offset into PRG of CPU `address`, or `None` when it's past the PRG the header declares
+    fn prg_offset(&self, address: u64) -> Option<u64> {
+        if let Some(bank) = self.bank {
+            let prg = bank * 0x4000 + (address & 0x3FFF);
+            return self.prg_size.is_none_or(|size| prg < size).then_some(prg);
+        }
+        let Some(prg_size) = self.prg_size else { return Some(address) };
+
+        let prg = match prg_size {
+            0 => return None,
+            // 16KB PRG is mirrored into both halves of $8000-$FFFF
+            size if size < 0x8000 => address % size,
+            _ => address,
+        };
+        (prg < prg_size).then_some(prg)
+    }
+}
+
+/// Whether `header`, a file's first 16 bytes, starts a Famicom Disk System
+/// image: fwNES's `FDS\x1A` header, or a raw disk side's first block
+pub fn is_fds(header: &[u8]) -> bool {
+    header.starts_with(b"FDS\x1A") || header.starts_with(b"\x01*NINTENDO-HVC*")
+}
+
+impl Mapping for Layout {
+    fn file_offset(&self, address: u32) -> Option<u64> {
+        Layout::file_offset(self, address)
+    }
+
+    fn file_offsets(&self, address: u32) -> Vec<u64> {
+        Layout::file_offsets(self, address)
+    }
+
+    fn header_len(&self) -> u64 {
+        self.prg_start
+    }
+
+    fn explain(&self, address: u32) -> Vec<String> {
+        let address = address as u64;
+        let mut steps = vec![format!("CPU address {:04X} (decoded {address:04X}, relative to $8000)", address | 0x8000)];
+        let header = match (self.prg_size, self.prg_start) {
+            (None, _) => "no iNES magic, assuming a 16 byte header".to_string(),
+            (_, 0x210) => "16 byte iNES header and 512 byte trainer, PRG at 210".to_string(),
+            (_, start) => format!("iNES header, PRG at {start:X}"),
+        };
+        steps.push(header);
+
+        let prg = match (self.bank, self.prg_size) {
+            (Some(bank), _) => {
+                steps.push(format!("--bank {bank}: {bank} x 4000 + ({address:04X} & 3FFF) = PRG {:X}", bank * 0x4000 + (address & 0x3FFF)));
+                bank * 0x4000 + (address & 0x3FFF)
+            },
+            (None, Some(size)) if size > 0x8000 => {
+                steps.push(format!("{size:X} bytes of PRG is {} 16KB banks; the mapper decides which is at $8000-$FFFF, so every bank gets N x 4000 + ({address:04X} & 3FFF)", size / 0x4000));
+                address & 0x3FFF
+            },
+            (None, Some(size)) if size > 0 && size < 0x8000 => {
+                steps.push(format!("{size:X} bytes of PRG mirrored across $8000-$FFFF: {address:04X} mod {size:X} = PRG {:X}", address % size));
+                address % size
+            },
+            (None, _) => {
+                steps.push(format!("$8000-$FFFF maps straight onto PRG: PRG {address:X}"));
+                address
+            },
+        };
+        match self.file_offsets(address as u32)[..] {
+            [] => steps.push(format!("PRG {prg:X} is past the end of PRG, so it isn't ROM")),
+            [offset] => steps.push(format!("file offset {:X} + {prg:X} = {offset:X}", self.prg_start)),
+            ref offsets => steps.push(format!("file offsets {}", offsets.iter().map(|offset| format!("{offset:X}")).collect::<Vec<_>>().join(", "))),
+        }
+        steps
+    }
+}
+
+// byte count for a header's bank count lsb/msb pair, including NES 2.0's exponent-multiplier form
+fn rom_size(lsb: u8, msb: u8, unit: u64) -> u64 {
+    if msb == 0x0F {
+        (1_u64 << (lsb >> 2)) * ((lsb & 0b11) as u64 * 2 + 1)
+    } else {
+        (((msb as u64) << 8) | lsb as u64) * unit
+    }
+}
+
+pub struct Nes;
+
+impl Console for Nes {
+    fn decode(&self, code: &str) -> Result<Patch, CodeError> {
+        parse_nes(code)
+    }
+
+    fn expected_lengths(&self) -> &'static [usize] {
+        NES_LENGTHS
+    }
+
+    fn alphabet(&self) -> &'static [char] {
+        &NES_CONVERSION
+    }
+
+    fn format(&self) -> String {
+        format!("6 or 8 letters of {}; 8 letter codes carry a compare byte", NES_CONVERSION.iter().collect::<String>())
+    }
+}
+
+// convert code chars to predesignated u8 values
+fn to_hex(code: &str, digits: &[char]) -> Result<Vec<u8>, CodeError> {
+    digits.iter().copied().enumerate().map(|(index, i)| {
+        match NES_CONVERSION.iter().position(|&c| c == i) {
+            Some(x) => Ok(x as u8),
+            None => Err(CodeError::InvalidChar { code: code.to_string(), index, found: i, expected: &NES_CONVERSION }),
+        }
+    }).collect()
+}
+
+pub fn parse_nes(code: &str) -> Result<Patch, CodeError> {
+    // count chars rather than bytes so non-ASCII input can't pass as a valid length
+    let digits: Vec<char> = normalize(code).chars().collect();
+
+    if digits.len() == 6 { //  unchecked code variation
+        let data_hex = to_hex(code, &digits)?;
+
+        // bit manupulation of u8s
+        /*
+            0000 1111 2222 3333 4444 5555
+            -333 4555 1222 3444 0111 5000 
+         */
+
+        let mut res_data: [u8; 6] = [0, 0, 0, 0, 0, 0];
+        
+        res_data[0] =  data_hex[3] & 0b0111;
+        res_data[1] = (data_hex[5] & 0b0111) + (data_hex[4] & 0b1000);
+        res_data[2] = (data_hex[2] & 0b0111) + (data_hex[1] & 0b1000);
+        res_data[3] = (data_hex[4] & 0b0111) + (data_hex[3] & 0b1000);
+        res_data[4] = (data_hex[1] & 0b0111) + (data_hex[0] & 0b1000);
+        res_data[5] = (data_hex[0] & 0b0111) + (data_hex[5] & 0b1000);
+    
+        let address: u32 = {
+            ((res_data[0] as u32) << (4 * 3)) +
+            ((res_data[1] as u32) << (4 * 2)) +
+            ((res_data[2] as u32) << 4) +
+             (res_data[3] as u32)
+        };
+
+        Ok(Patch { address, value: vec![(res_data[4] << 4) + res_data[5]], compare: None })
+    } else if digits.len() == 8 { // checked code variation
+        let data_hex = to_hex(code, &digits)?;
+
+        // bit manupulation of u8s
+        /*
+            0000 1111 2222 3333 4444 5555 6666 7777
+            -333 4555 1222 3444 0111 7000 6777 5666
+         */
+
+        let mut res_data: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+        
+        res_data[0] =  data_hex[3] & 0b0111;
+        res_data[1] = (data_hex[5] & 0b0111) + (data_hex[4] & 0b1000);
+        res_data[2] = (data_hex[2] & 0b0111) + (data_hex[1] & 0b1000);
+        res_data[3] = (data_hex[4] & 0b0111) + (data_hex[3] & 0b1000);
+        res_data[4] = (data_hex[1] & 0b0111) + (data_hex[0] & 0b1000);
+        res_data[5] = (data_hex[0] & 0b0111) + (data_hex[7] & 0b1000);
+        res_data[6] = (data_hex[7] & 0b0111) + (data_hex[6] & 0b1000);
+        res_data[7] = (data_hex[6] & 0b0111) + (data_hex[5] & 0b1000);
+    
+        let address: u32 = {
+            ((res_data[0] as u32) << (4 * 3)) +
+            ((res_data[1] as u32) << (4 * 2)) +
+            ((res_data[2] as u32) << 4) +
+             (res_data[3] as u32)
+        };
+
+        Ok(Patch { address, value: vec![(res_data[4] << 4) + res_data[5]], compare: Some((res_data[6] << 4) + res_data[7]) })
+    } else { // invalid state
+        Err(CodeError::BadLength { code: code.to_string(), length: digits.len(), expected: NES_LENGTHS })
+    }
+}
+
+/// Build the 6 letter code (8 with a compare byte) for `patch`. The address
+/// is the 15 bit one `parse_nes` gives back; CPU addresses in `$8000-$FFFF`
+/// are accepted too. `None` when the address is past `$FFFF` or the value
+/// isn't a single byte
+pub fn encode_nes(patch: &Patch) -> Option<String> {
+    let [value] = patch.value[..] else { return None };
+    if patch.address > 0xFFFF { return None; }
+
+    let nibble = |n: u32, i: u32| ((n >> (i * 4)) & 0xF) as u8;
+    let (address, value) = (patch.address, value as u32);
+
+    // the inverse of parse_nes; the high bit of the third letter marks an 8 letter code
+    let mut data_hex: Vec<u8> = vec![
+        (nibble(value, 0) & 0b0111) + (nibble(value, 1) & 0b1000),
+        (nibble(value, 1) & 0b0111) + (nibble(address, 1) & 0b1000),
+        (nibble(address, 1) & 0b0111) + if patch.compare.is_some() { 0b1000 } else { 0 },
+        (nibble(address, 3) & 0b0111) + (nibble(address, 0) & 0b1000),
+        (nibble(address, 0) & 0b0111) + (nibble(address, 2) & 0b1000),
+        (nibble(address, 2) & 0b0111) + (nibble(value, 0) & 0b1000),
+    ];
+    if let Some(compare) = patch.compare {
+        let compare = compare as u32;
+        data_hex[5] = (data_hex[5] & 0b0111) + (nibble(compare, 0) & 0b1000);
+        data_hex.push((nibble(compare, 0) & 0b0111) + (nibble(compare, 1) & 0b1000));
+        data_hex.push((nibble(compare, 1) & 0b0111) + (nibble(value, 0) & 0b1000));
+    }
+
+    Some(data_hex.iter().map(|&x| NES_CONVERSION[x as usize]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    #[test]
+    fn random_patches_round_trip() {
+        let mut rng = Rng::new(0x4E45);
+        for _ in 0..2000 {
+            // codes carry 15 address bits, relative to $8000
+            let patch = Patch { address: rng.below(0x8000) as u32, value: vec![rng.byte()], compare: rng.maybe_byte() };
+            let code = encode_nes(&patch).unwrap();
+            assert_eq!(code.len(), if patch.compare.is_some() { 8 } else { 6 });
+            assert_eq!(parse_nes(&code).unwrap(), patch, "{code}");
+        }
+    }
+
+    #[test]
+    fn decodes_a_six_letter_code() {
+        assert_eq!(parse_nes("SXIOPO").unwrap(), Patch { address: 0x11D9, value: vec![0xAD], compare: None });
+    }
+
+    #[test]
+    fn rejects_empty_and_malformed_codes() {
+        for (code, length) in [("", 0), ("-", 0), ("SXIOPOSXIOPOSXIOPOSX", 20)] {
+            assert!(matches!(parse_nes(code), Err(CodeError::BadLength { length: l, .. }) if l == length), "{code:?}");
+        }
+        assert!(matches!(parse_nes("SXIOPQ"), Err(CodeError::InvalidChar { index: 5, found: 'Q', .. })));
+    }
+
+    #[test]
+    fn encodes_a_six_letter_code_back() {
+        assert_eq!(encode_nes(&Patch { address: 0x11D9, value: vec![0xAD], compare: None }).as_deref(), Some("SXIOPO"));
+    }
+
+    #[test]
+    fn maps_past_an_ines_header() {
+        let layout = Layout { prg_start: 0x10, prg_size: Some(0x8000), chr_size: Some(0), bank: None, size: 0x8010 };
+        assert_eq!(layout.file_offset(0x11D9), Some(0x11E9));
+    }
+
+    // an iNES header declaring `prg` 16KB PRG banks and `chr` 8KB CHR banks, followed by both
+    fn ines(prg: u8, chr: u8) -> Vec<u8> {
+        let mut rom = vec![0_u8; 0x10 + prg as usize * 0x4000 + chr as usize * 0x2000];
+        rom[..6].copy_from_slice(&[b'N', b'E', b'S', 0x1A, prg, chr]);
+        rom
+    }
+
+    #[test]
+    fn reads_prg_and_chr_sizes_from_the_header() {
+        let layout = Layout::detect(&ines(2, 1)[..]).unwrap();
+        assert_eq!((layout.prg_start, layout.prg_size, layout.chr_size, layout.size), (0x10, Some(0x8000), Some(0x2000), 0xA010));
+        assert_eq!(layout.size_problem(0xA010), None);
+    }
+
+    #[test]
+    fn never_maps_into_chr() {
+        let rom = ines(2, 1);
+        let layout = Layout::detect(&rom[..]).unwrap();
+        let chr_start = 0x10 + 0x8000;
+        for address in (0..0x8000).step_by(0x123).chain([0x7FFF]) {
+            assert!(layout.file_offset(address).is_some_and(|offset| offset < chr_start), "{address:X}");
+        }
+        // a bank past PRG would be CHR
+        assert_eq!(Layout { bank: Some(2), ..layout }.file_offset(0x11D9), None);
+        assert_eq!(Layout { bank: Some(1), ..layout }.file_offset(0x11D9), Some(0x10 + 0x4000 + 0x11D9));
+    }
+
+    #[test]
+    fn mirrors_16k_of_prg_ahead_of_chr() {
+        let layout = Layout::detect(&ines(1, 1)[..]).unwrap();
+        assert_eq!(layout.file_offset(0x11D9), Some(0x11E9));
+        assert_eq!(layout.file_offset(0x51D9), Some(0x11E9));
+    }
+
+    #[test]
+    fn a_headerless_file_ends_its_prg() {
+        let layout = Layout::detect(&[0_u8; 64][..]).unwrap();
+        assert_eq!(layout.prg_size, None);
+        assert_eq!(layout.file_offset(0x11D9), None);
+        assert_eq!(layout.file_offset(0x2F), Some(0x3F));
+        assert_eq!(layout.file_offset(0x30), None);
+        assert_eq!(Layout { bank: Some(0), ..layout }.file_offset(0x11D9), None);
+    }
+}
@@ -0,0 +1,96 @@
+use std::io;
+
+use crate::{mode::Mode, rom::Image};
+
+/// A decoded Game Genie code: write `value` at `address`, optionally only
+/// when the byte already there equals `compare`. `value` is one byte for
+/// every system but the Genesis, whose codes write a big endian word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub address: u32,
+    pub value: Vec<u8>,
+    pub compare: Option<u8>,
+}
+
+/// Bytes as one run of uppercase hex digits, e.g. `AD` or `5478`
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyStatus {
+    Applied,
+    /// The ROM already held the code's value, so nothing was written
+    SkippedNoop,
+    /// The byte at the target didn't match the code's compare value
+    SkippedCompareMismatch { found: u8 },
+    /// The compare value didn't match, but the code was written anyway
+    Forced { found: u8 },
+    /// Switched off in its code file, so it was left out
+    Disabled,
+    /// A guard code on its line found `found` rather than its compare byte,
+    /// so the line's codes were left out
+    SkippedGuard { found: u8 },
+}
+
+impl ApplyStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApplyStatus::Applied => "applied",
+            ApplyStatus::SkippedNoop => "skipped-noop",
+            ApplyStatus::SkippedCompareMismatch { .. } => "skipped-compare-mismatch",
+            ApplyStatus::Forced { .. } => "forced",
+            ApplyStatus::Disabled => "disabled",
+            ApplyStatus::SkippedGuard { .. } => "skipped-guard",
+        }
+    }
+}
+
+/// What happened when one code was applied, for summaries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchResult {
+    pub code: String,
+    pub system: Mode,
+    pub address: u32,
+    pub file_offset: u64,
+    /// The bytes that were at `file_offset` before, as many as `new` holds
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+    pub status: ApplyStatus,
+    /// The cheat the code belongs to, when it came from a labelled line of a code file
+    pub label: Option<String>,
+}
+
+impl PatchResult {
+    pub fn new(code: &str, system: Mode, file_offset: u64, patch: &Patch, applied: Applied) -> PatchResult {
+        PatchResult { code: code.to_string(), system, address: patch.address, file_offset, old: applied.old, new: patch.value.clone(), status: applied.status, label: None }
+    }
+}
+
+/// What `apply` found at the target and what it did about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Applied {
+    pub old: Vec<u8>,
+    pub status: ApplyStatus,
+}
+
+/// Write `patch` to `image` at file offset `offset`, honouring its compare
+/// byte unless `force` is set. The bytes already there are always read
+/// first, so a patch that changes nothing isn't written
+pub fn apply<I: Image + ?Sized>(image: &mut I, offset: u64, patch: &Patch, force: bool) -> io::Result<Applied> {
+    let mut old = vec![0_u8; patch.value.len()];
+    image.read_bytes(&mut old, offset)?;
+
+    let found = old[0];
+    let status = match patch.compare {
+        Some(compare) if found != compare && !force => ApplyStatus::SkippedCompareMismatch { found },
+        Some(compare) if found != compare => ApplyStatus::Forced { found },
+        _ if old == patch.value => ApplyStatus::SkippedNoop,
+        _ => ApplyStatus::Applied,
+    };
+
+    if matches!(status, ApplyStatus::Applied | ApplyStatus::Forced { .. }) {
+        image.write_bytes(&patch.value, offset)?;
+    }
+    Ok(Applied { old, status })
+}
@@ -0,0 +1,55 @@
+//! A one-line progress bar on stderr for long code lists. It only appears
+//! when stderr is a terminal and info messages aren't silenced, and the
+//! logger wipes it before printing so the two never share a line.
+
+use std::{io::{self, IsTerminal, Write}, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::log::{self, Level};
+
+/// Jobs smaller than this finish too fast for a bar to be worth drawing
+pub const MIN_TOTAL: usize = 100;
+
+const WIDTH: usize = 30;
+
+// whether a bar is on screen right now
+static DRAWN: AtomicBool = AtomicBool::new(false);
+
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    done: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize) -> Progress {
+        let enabled = total >= MIN_TOTAL && log::enabled(Level::Info) && io::stderr().is_terminal();
+        Progress { label, total, done: 0, enabled }
+    }
+
+    pub fn tick(&mut self) {
+        self.done += 1;
+        // redraw only when the bar actually grows, or at the end
+        if !self.enabled || (self.done * WIDTH / self.total == (self.done - 1) * WIDTH / self.total && self.done != self.total) {
+            return;
+        }
+
+        let filled = self.done * WIDTH / self.total;
+        eprint!("\r[{}{}] {}/{} {}", "#".repeat(filled), " ".repeat(WIDTH - filled), self.done, self.total, self.label);
+        let _ = io::stderr().flush();
+        DRAWN.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled { clear(); }
+    }
+}
+
+/// Erase the bar, if one is showing
+pub fn clear() {
+    if DRAWN.swap(false, Ordering::Relaxed) {
+        eprint!("\r\x1B[2K");
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashSet, fmt, io::{self, Write}};
+
+use clap::ValueEnum;
+
+use crate::{codes::{format_entry, ListEntry}, error::CodeError, mode::Mode, patch::{hex, ApplyStatus, Patch, PatchResult}};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SummaryFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+pub fn write_summary(w: &mut impl Write, format: SummaryFormat, results: &[PatchResult]) -> io::Result<()> {
+    match format {
+        SummaryFormat::Text => write_text(w, results),
+        SummaryFormat::Json => write_json(w, results),
+        SummaryFormat::Csv => write_csv(w, results),
+    }
+}
+
+pub fn write_text(w: &mut impl Write, results: &[PatchResult]) -> io::Result<()> {
+    for result in results {
+        writeln!(
+            w, "{}\t{}\t{:06X} @ {:X}\t{} -> {}\t{}{}",
+            result.code, result.system, result.address, result.file_offset, hex(&result.old), hex(&result.new), result.status.name(),
+            result.label.as_ref().map_or(String::new(), |label| format!("\t{label}")),
+        )?;
+    }
+    Ok(())
+}
+
+/// A dry run's changes, one `offset: old -> new` line per patch that would be
+/// written (with its code), and the patches that wouldn't with their status
+pub fn write_diff(w: &mut impl Write, results: &[PatchResult]) -> io::Result<()> {
+    for result in results {
+        match result.status {
+            ApplyStatus::Applied | ApplyStatus::Forced { .. } => {
+                writeln!(w, "{:X}: {} -> {}\t{}", result.file_offset, hex(&result.old), hex(&result.new), result.code)?;
+            },
+            status => writeln!(w, "{:X}: {} unchanged\t{}\t{}", result.file_offset, hex(&result.old), result.code, status.name())?,
+        }
+    }
+    Ok(())
+}
+
+// quote a field when it holds a delimiter, quote or newline, doubling any quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A run's footprint: how many patches went in and how many didn't, and the
+/// file bytes they wrote. More bytes written than unique offsets means codes overlapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tally {
+    /// Written, with or without `--force`
+    pub applied: usize,
+    /// Everything else: unchanged, mismatched, guarded or disabled
+    pub skipped: usize,
+    pub unique_offsets: usize,
+    pub bytes_written: usize,
+}
+
+pub fn tally(results: &[PatchResult]) -> Tally {
+    let mut offsets = HashSet::new();
+    let mut tally = Tally { applied: 0, skipped: 0, unique_offsets: 0, bytes_written: 0 };
+    for result in results {
+        match result.status {
+            ApplyStatus::Applied | ApplyStatus::Forced { .. } => {
+                tally.applied += 1;
+                tally.bytes_written += result.new.len();
+                offsets.extend((0..result.new.len() as u64).map(|i| result.file_offset + i));
+            },
+            _ => tally.skipped += 1,
+        }
+    }
+    tally.unique_offsets = offsets.len();
+    tally
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} applied, {} skipped, {} unique file offsets modified, {} bytes written", self.applied, self.skipped, self.unique_offsets, self.bytes_written)?;
+        if self.bytes_written > self.unique_offsets { write!(f, " (some codes overlap)")?; }
+        Ok(())
+    }
+}
+
+fn json_tally(tally: &Tally) -> String {
+    format!(
+        "{{\"applied\": {}, \"skipped\": {}, \"unique_offsets\": {}, \"bytes_written\": {}}}",
+        tally.applied, tally.skipped, tally.unique_offsets, tally.bytes_written,
+    )
+}
+
+/// One row per patch; numbers are 0x-prefixed so spreadsheets keep them as
+/// hex text. The tally follows as a `#` comment line
+pub fn write_csv(w: &mut impl Write, results: &[PatchResult]) -> io::Result<()> {
+    writeln!(w, "code,system,address,file_offset,old,new,status,label")?;
+    for result in results {
+        writeln!(
+            w, "{},{},0x{:06X},0x{:X},0x{},0x{},{},{}",
+            csv_field(&result.code), csv_field(&result.system.to_string()), result.address, result.file_offset,
+            hex(&result.old), hex(&result.new), result.status.name(), csv_field(result.label.as_deref().unwrap_or("")),
+        )?;
+    }
+    writeln!(w, "# {}", tally(results))
+}
+
+/// A code list decoded without a ROM, one `(code, decoded)` pair per entry
+pub type Catalog<'a> = [(&'a ListEntry, Result<(Mode, Patch), CodeError>)];
+
+// a catalog row's fields: label, code, system, address, value, compare and error, blank where they don't apply
+fn catalog_fields(entry: &ListEntry, decoded: &Result<(Mode, Patch), CodeError>, default: Mode) -> [String; 7] {
+    let label = entry.label.clone().unwrap_or_default();
+    let code = format_entry(&entry.code, default);
+    match decoded {
+        Ok((mode, patch)) => [
+            label, code, mode.to_string(), format!("0x{:06X}", patch.address), format!("0x{}", hex(&patch.value)),
+            patch.compare.map_or(String::new(), |compare| format!("0x{compare:02X}")), String::new(),
+        ],
+        Err(err) => [label, code, String::new(), String::new(), String::new(), String::new(), err.to_string()],
+    }
+}
+
+/// The catalog as CSV, with the error for a code that doesn't decode in
+/// its last column instead of its patch
+pub fn write_catalog_csv(w: &mut impl Write, catalog: &Catalog, default: Mode) -> io::Result<()> {
+    writeln!(w, "label,code,system,address,value,compare,error")?;
+    for (entry, decoded) in catalog {
+        let fields = catalog_fields(entry, decoded, default).map(|field| csv_field(&field));
+        writeln!(w, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// The catalog as tab-separated text, in the same columns as the CSV
+pub fn write_catalog_text(w: &mut impl Write, catalog: &Catalog, default: Mode) -> io::Result<()> {
+    for (entry, decoded) in catalog {
+        writeln!(w, "{}", catalog_fields(entry, decoded, default).join("\t").trim_end())?;
+    }
+    Ok(())
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// bytes as one big endian number, so a Genesis word reads as the value it writes
+fn number(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |n, &byte| (n << 8) | byte as u64)
+}
+
+fn json_object(result: &PatchResult) -> String {
+    format!(
+        "{{\"code\": {}, \"system\": {}, \"address\": {}, \"file_offset\": {}, \"old\": {}, \"new\": {}, \"status\": {}, \"label\": {}}}",
+        json_string(&result.code), json_string(&result.system.to_string()), result.address, result.file_offset,
+        number(&result.old), number(&result.new), json_string(result.status.name()),
+        result.label.as_deref().map_or("null".to_string(), json_string),
+    )
+}
+
+/// `{"patches": [...], "tally": {...}}`, an object per patch and the run's tally
+pub fn write_json(w: &mut impl Write, results: &[PatchResult]) -> io::Result<()> {
+    writeln!(w, "{{\"patches\": [")?;
+    for (i, result) in results.iter().enumerate() {
+        writeln!(w, "  {}{}", json_object(result), if i + 1 < results.len() { "," } else { "" })?;
+    }
+    writeln!(w, "], \"tally\": {}}}", json_tally(&tally(results)))
+}
+
+/// How one system's codes went in a run that patched several
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemSummary {
+    pub system: Mode,
+    /// Written, with or without `--force`
+    pub applied: usize,
+    /// The ROM already held the value
+    pub unchanged: usize,
+    /// Left out because their compare byte, or their guard's, didn't match
+    pub skipped: usize,
+    /// Switched off in their code file
+    pub disabled: usize,
+    pub results: Vec<PatchResult>,
+}
+
+/// Split `results` by system, in the order each system first appears
+pub fn by_system(results: &[PatchResult]) -> Vec<SystemSummary> {
+    let mut systems: Vec<SystemSummary> = Vec::new();
+    for result in results {
+        let index = match systems.iter().position(|summary| summary.system == result.system) {
+            Some(index) => index,
+            None => {
+                systems.push(SystemSummary { system: result.system, applied: 0, unchanged: 0, skipped: 0, disabled: 0, results: Vec::new() });
+                systems.len() - 1
+            },
+        };
+        let summary = &mut systems[index];
+        match result.status {
+            ApplyStatus::Applied | ApplyStatus::Forced { .. } => summary.applied += 1,
+            ApplyStatus::SkippedNoop => summary.unchanged += 1,
+            ApplyStatus::SkippedCompareMismatch { .. } | ApplyStatus::SkippedGuard { .. } => summary.skipped += 1,
+            ApplyStatus::Disabled => summary.disabled += 1,
+        }
+        summary.results.push(result.clone());
+    }
+    systems
+}
+
+/// The summary, grouped by system: a heading with counts per system in
+/// text, an object per system in JSON, and rows ordered by system in CSV
+pub fn write_by_system(w: &mut impl Write, format: SummaryFormat, results: &[PatchResult]) -> io::Result<()> {
+    let systems = by_system(results);
+    match format {
+        SummaryFormat::Text => {
+            for summary in &systems {
+                let disabled = if summary.disabled > 0 { format!(", {} disabled", summary.disabled) } else { String::new() };
+                writeln!(w, "{}: {} applied, {} unchanged, {} skipped{disabled}", summary.system, summary.applied, summary.unchanged, summary.skipped)?;
+                write_text(w, &summary.results)?;
+            }
+            Ok(())
+        },
+        SummaryFormat::Csv => write_csv(w, &systems.into_iter().flat_map(|summary| summary.results).collect::<Vec<_>>()),
+        SummaryFormat::Json => {
+            writeln!(w, "{{")?;
+            for summary in &systems {
+                writeln!(
+                    w, "  {}: {{\"applied\": {}, \"unchanged\": {}, \"skipped\": {}, \"disabled\": {}, \"patches\": [",
+                    json_string(&summary.system.to_string()), summary.applied, summary.unchanged, summary.skipped, summary.disabled,
+                )?;
+                for (j, result) in summary.results.iter().enumerate() {
+                    writeln!(w, "    {}{}", json_object(result), if j + 1 < summary.results.len() { "," } else { "" })?;
+                }
+                writeln!(w, "  ]}},")?;
+            }
+            // no system is called tally, so it can't clash with one
+            writeln!(w, "  \"tally\": {}", json_tally(&tally(results)))?;
+            writeln!(w, "}}")
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::Applied;
+
+    fn result(offset: u64, value: &[u8], status: ApplyStatus) -> PatchResult {
+        let patch = Patch { address: 0, value: value.to_vec(), compare: None };
+        PatchResult::new("CODE", Mode::Genesis, offset, &patch, Applied { old: vec![0; value.len()], status })
+    }
+
+    #[test]
+    fn tallies_overlapping_writes() {
+        let results = [
+            result(0x200, &[0x4E, 0x71], ApplyStatus::Applied),
+            result(0x201, &[0x60, 0x00], ApplyStatus::Forced { found: 0 }),
+            result(0x300, &[0x00, 0x00], ApplyStatus::SkippedNoop),
+        ];
+        let tally = tally(&results);
+        assert_eq!(tally, Tally { applied: 2, skipped: 1, unique_offsets: 3, bytes_written: 4 });
+        assert_eq!(tally.to_string(), "2 applied, 1 skipped, 3 unique file offsets modified, 4 bytes written (some codes overlap)");
+    }
+
+    #[test]
+    fn summaries_end_with_the_tally() {
+        let results = [result(0x200, &[0x4E, 0x71], ApplyStatus::Applied)];
+        let (mut json, mut csv) = (Vec::new(), Vec::new());
+        write_json(&mut json, &results).unwrap();
+        write_csv(&mut csv, &results).unwrap();
+        let (json, csv) = (String::from_utf8(json).unwrap(), String::from_utf8(csv).unwrap());
+        assert!(json.starts_with("{\"patches\": [\n"), "{json}");
+        assert!(json.ends_with("], \"tally\": {\"applied\": 1, \"skipped\": 0, \"unique_offsets\": 2, \"bytes_written\": 2}}\n"), "{json}");
+        assert_eq!(csv.lines().last(), Some("# 1 applied, 0 skipped, 2 unique file offsets modified, 2 bytes written"), "{csv}");
+    }
+}
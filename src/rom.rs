@@ -0,0 +1,123 @@
+use std::{fs::File, io, os::unix::fs::FileExt};
+
+use crate::{mode::Mode, nes};
+
+/// Byte-addressable ROM storage that patches are read from and written to.
+///
+/// Reads past the end leave the rest of `buf` untouched and writes past the
+/// end grow the image, the same way `pread`/`pwrite` treat a file.
+pub trait Image {
+    fn size(&self) -> io::Result<u64>;
+    fn read_bytes(&self, buf: &mut [u8], offset: u64) -> io::Result<()>;
+    fn write_bytes(&mut self, buf: &[u8], offset: u64) -> io::Result<()>;
+}
+
+impl Image for File {
+    fn size(&self) -> io::Result<u64> {
+        self.metadata().map(|m| m.len())
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.read_at(buf, offset).map(|_| ())
+    }
+
+    fn write_bytes(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+        self.write_all_at(buf, offset)
+    }
+}
+
+// a fixed size buffer, which can't grow to take writes past its end
+impl Image for [u8] {
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        let start = (offset as usize).min(self.len());
+        let end = (start + buf.len()).min(self.len());
+        buf[..end - start].copy_from_slice(&self[start..end]);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+        let start = offset as usize;
+        let Some(target) = self.get_mut(start..start + buf.len()) else {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "write past the end of the ROM buffer"));
+        };
+        target.copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+impl Image for Vec<u8> {
+    fn size(&self) -> io::Result<u64> {
+        self.as_slice().size()
+    }
+
+    fn read_bytes(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.as_slice().read_bytes(buf, offset)
+    }
+
+    fn write_bytes(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+        let start = offset as usize;
+        if self.len() < start + buf.len() { self.resize(start + buf.len(), 0); }
+        self[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+// the first bytes of the Nintendo logo every Game Boy cartridge carries at 0x104
+const GAMEBOY_LOGO: [u8; 8] = [0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B];
+
+/// The system `image` looks like from its magic bytes, with what gave it
+/// away, or `None` when nothing matches. Only a cheap sanity check: the
+/// NES, Genesis and Game Boy markers are reliable, a SNES one is a header
+/// checksum that happens to agree with its complement
+pub fn sniff<I: Image + ?Sized>(image: &I) -> io::Result<Option<(Mode, &'static str)>> {
+    let mut start = [0_u8; 0x200];
+    image.read_bytes(&mut start, 0)?;
+    let at = |offset: u64, len: usize| -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0_u8; len];
+        image.read_bytes(&mut bytes, offset)?;
+        Ok(bytes)
+    };
+
+    if start.starts_with(b"NES\x1A") { return Ok(Some((Mode::Nintendo, "an iNES header"))); }
+    if nes::is_fds(&start) { return Ok(Some((Mode::Nintendo, "a Famicom Disk System header"))); }
+    if matches!(&start[0x100..0x104], b"SEGA" | b"ESAG") { return Ok(Some((Mode::Genesis, "SEGA at 0x100"))); }
+    if start[0x104..0x10C] == GAMEBOY_LOGO { return Ok(Some((Mode::GameBoy, "the Nintendo logo at 0x104"))); }
+    for offset in [0x7FF0, 0x3FF0, 0x1FF0] {
+        if at(offset, 8)? == b"TMR SEGA" { return Ok(Some((Mode::GameGear, "TMR SEGA near the end of the first 32KB"))); }
+    }
+    let size = image.size()?;
+    let copier = if size % 0x400 == 0x200 { 0x200 } else { 0 };
+    for offset in [0x7FDC, 0xFFDC] {
+        let sums = at(copier + offset, 4)?;
+        let (complement, checksum) = (u16::from_le_bytes([sums[0], sums[1]]), u16::from_le_bytes([sums[2], sums[3]]));
+        if complement ^ checksum == 0xFFFF && checksum != 0 && complement != 0 {
+            return Ok(Some((Mode::SuperNintendo, "a SNES header checksum and complement")));
+        }
+    }
+    Ok(None)
+}
+
+/// How a system's bus addresses land in a particular ROM file
+pub trait Mapping {
+    /// File offset of bus `address`, or `None` when it isn't backed by ROM
+    fn file_offset(&self, address: u32) -> Option<u64>;
+    /// Every file offset bus `address` can reach, for mappings where it
+    /// depends on which bank is switched in; the first is `file_offset`'s
+    fn file_offsets(&self, address: u32) -> Vec<u64> {
+        self.file_offset(address).into_iter().collect()
+    }
+    /// Bytes at the start of the file that belong to a header rather than ROM data
+    fn header_len(&self) -> u64;
+    /// The steps from bus `address` to its file offsets, one line each, for
+    /// `--explain-offsets`
+    fn explain(&self, address: u32) -> Vec<String> {
+        match self.file_offsets(address)[..] {
+            [] => vec![format!("{address:06X} isn't backed by ROM")],
+            ref offsets => vec![format!("file offset {}", offsets.iter().map(|offset| format!("{offset:X}")).collect::<Vec<_>>().join(", "))],
+        }
+    }
+}
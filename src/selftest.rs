@@ -0,0 +1,86 @@
+//! Published codes with known patches, decoded (and encoded back) at runtime
+//! by `rggp selftest` so a build can be checked without a ROM.
+
+use crate::{codes::{decode_entry, normalize}, gameboy::{self, encode_gameboy}, genesis::{check_char, encode_genesis, Word}, mode::Mode, nes::encode_nes, patch::{hex, Patch}, snes::encode_snes};
+
+/// A code and the patch it's documented to make
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vector {
+    pub mode: Mode,
+    pub code: &'static str,
+    pub address: u32,
+    pub value: &'static [u8],
+    pub compare: Option<u8>,
+}
+
+/// One published code per system, and the other forms of the same codes,
+/// so each system's bit shuffle and every code length is covered
+pub const VECTORS: &[Vector] = &[
+    Vector { mode: Mode::Nintendo, code: "SXIOPO", address: 0x11D9, value: &[0xAD], compare: None },
+    // the 8 letter form, with a compare byte: $94A7 = 02 where the ROM holds 03
+    Vector { mode: Mode::Nintendo, code: "ZEXPYGLA", address: 0x14A7, value: &[0x02], compare: Some(0x03) },
+    Vector { mode: Mode::SuperNintendo, code: "C264-64D7", address: 0x00_8E28, value: &[0xAD], compare: None },
+    Vector { mode: Mode::Genesis, code: "SCRA-BJX0", address: 0x00_9C76, value: &[0x54, 0x78], compare: None },
+    // with rggp's check character: SCRABJX0's values sum to 81, and 81 mod 32 is 17, V
+    Vector { mode: Mode::Genesis, code: "SCRA-BJX0-V", address: 0x00_9C76, value: &[0x54, 0x78], compare: None },
+    Vector { mode: Mode::GameBoy, code: "00A-17B-C49", address: 0x4A17, value: &[0x00], compare: Some(0xC8) },
+    // the same code without its compare byte, and on the Game Gear, which shares the format
+    Vector { mode: Mode::GameBoy, code: "00A-17B", address: 0x4A17, value: &[0x00], compare: None },
+    Vector { mode: Mode::GameGear, code: "00A-17B-C49", address: 0x4A17, value: &[0x00], compare: Some(0xC8) },
+];
+
+impl Vector {
+    pub fn patch(&self) -> Patch {
+        Patch { address: self.address, value: self.value.to_vec(), compare: self.compare }
+    }
+
+    /// Decode the code and encode its patch back, describing the first
+    /// thing that doesn't come out as documented
+    pub fn check(&self) -> Result<(), String> {
+        let expected = self.patch();
+        let (_, decoded) = decode_entry(self.code, self.mode).map_err(|err| err.to_string())?;
+        if decoded != expected {
+            return Err(format!("decoded to {:06X} {} (compare {:?}), expected {:06X} {}", decoded.address, hex(&decoded.value), decoded.compare, self.address, hex(self.value)));
+        }
+
+        let encoded = match self.mode {
+            Mode::Nintendo => encode_nes(&expected),
+            Mode::SuperNintendo => encode_snes(&expected),
+            Mode::GameBoy | Mode::GameGear => gameboy::rom_end(self.mode).and_then(|rom_end| encode_gameboy(&expected, rom_end)),
+            Mode::Genesis => encode_genesis(&Word { address: self.address, value: u16::from_be_bytes([self.value[0], self.value[1]]) }).map(|code| {
+                // encode_genesis leaves the check character off, so put it back for codes documented with one
+                match normalize(self.code).len() {
+                    9 => format!("{code}-{}", check_char(&normalize(&code).chars().collect::<Vec<_>>()).unwrap_or('?')),
+                    _ => code,
+                }
+            }),
+            Mode::MasterSystem => None,
+        };
+        match encoded {
+            Some(code) if code == self.code => Ok(()),
+            Some(code) => Err(format!("encoded back as {code}")),
+            None => Err("didn't encode back".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_vector_decodes_and_encodes_back() {
+        for vector in VECTORS {
+            assert_eq!(vector.check(), Ok(()), "{}", vector.code);
+        }
+    }
+
+    #[test]
+    fn every_code_length_is_covered() {
+        for (mode, lengths) in [(Mode::Nintendo, [6, 8]), (Mode::GameBoy, [6, 9]), (Mode::Genesis, [8, 9])] {
+            for length in lengths {
+                assert!(VECTORS.iter().any(|vector| vector.mode == mode && normalize(vector.code).len() == length), "{mode} {length}");
+            }
+        }
+    }
+}
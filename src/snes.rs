@@ -0,0 +1,271 @@
+use std::io;
+
+use clap::ValueEnum;
+
+use crate::{codes::normalize, console::Console, debug, error::CodeError, patch::Patch, rom::{Image, Mapping}};
+
+// the SNES Game Genie's hex digits, in value order
+pub const SNES_CONVERSION: [char; 16] = ['D', 'F', '4', '7', '0', '9', '1', '5', '6', 'B', 'C', '8', 'A', '2', '3', 'E'];
+
+/// Always 8 characters; SNES codes have no compare byte
+pub const SNES_LENGTHS: &[usize] = &[8];
+
+/// How the cartridge maps bus addresses onto ROM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SnesMap {
+    /// Pick LoROM or HiROM from the internal header
+    Auto,
+    #[value(name = "lorom")]
+    LoRom,
+    #[value(name = "hirom")]
+    HiRom,
+    /// HiROM extended past 4MB, with the upper half of the ROM in banks `$40-$7D`
+    #[value(name = "exhirom")]
+    ExHiRom,
+}
+
+/// Where a SNES ROM's data starts in the file and how addresses map onto it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// Size of the 512 byte copier header, if the dump has one
+    pub header: u64,
+    pub map: SnesMap,
+    /// File length; codes mapped past it aren't backed by ROM
+    pub size: u64,
+}
+
+impl Layout {
+    pub fn detect<I: Image + ?Sized>(image: &I, map: SnesMap) -> io::Result<Layout> {
+        let size = image.size()?;
+        // copier headers pad the file to 512 bytes past a multiple of 1KB
+        let header = if size % 0x400 == 0x200 { 0x200 } else { 0 };
+
+        let map = match map {
+            SnesMap::Auto => {
+                let lo = header_score(image, header + 0x7FC0, &[0x0, 0x2, 0x3])?;
+                let hi = header_score(image, header + 0xFFC0, &[0x1, 0xA])?;
+                // ExHiROM keeps its header 4MB in, so only big dumps can have one
+                let ex = if size > header + 0x40_0000 { header_score(image, header + 0x40_FFC0, &[0x5])? } else { 0 };
+
+                if ex > hi.max(lo) { SnesMap::ExHiRom } else if hi > lo { SnesMap::HiRom } else { SnesMap::LoRom }
+            },
+            map => map,
+        };
+        debug!("SNES layout: {map:?}, {header:#X} byte copier header");
+
+        Ok(Layout { header, map, size })
+    }
+
+    /// File offset of bus `address`, or `None` when it isn't backed by ROM
+    pub fn file_offset(&self, address: u32) -> Option<u64> {
+        self.mapped(address).filter(|&offset| offset < self.size)
+    }
+
+    // where the map puts bus `address` in the file, whether or not the file is that long
+    fn mapped(&self, address: u32) -> Option<u64> {
+        let bank = address >> 16;
+        let offset = address & 0xFFFF;
+        if matches!(bank, 0x7E | 0x7F) { return None; } // work RAM
+
+        let rom_offset = match self.map {
+            SnesMap::HiRom if bank & 0x40 != 0 => address & 0x3F_FFFF,
+            SnesMap::HiRom if offset >= 0x8000 => ((bank & 0x3F) << 16) | offset,
+            // banks $C0-$FF hold the first 4MB and $40-$7D the rest, each mirrored into the upper half of the bank 64 below
+            SnesMap::ExHiRom if bank & 0x40 != 0 || offset >= 0x8000 => {
+                let upper = if bank & 0x80 == 0 { 0x40_0000 } else { 0 };
+                upper | ((bank & 0x3F) << 16) | offset
+            },
+            SnesMap::LoRom | SnesMap::Auto if offset >= 0x8000 => ((bank & 0x7F) << 15) | (offset & 0x7FFF),
+            _ => return None,
+        };
+
+        Some(self.header + rom_offset as u64)
+    }
+}
+
+impl Mapping for Layout {
+    fn file_offset(&self, address: u32) -> Option<u64> {
+        Layout::file_offset(self, address)
+    }
+
+    fn header_len(&self) -> u64 {
+        self.header
+    }
+
+    fn explain(&self, address: u32) -> Vec<String> {
+        let (bank, offset) = (address >> 16, address & 0xFFFF);
+        let mut steps = vec![format!("bus address {address:06X}: bank {bank:02X}, offset {offset:04X}")];
+        let Some(file_offset) = self.mapped(address) else {
+            steps.push(format!("{:?} maps no ROM there", self.map));
+            return steps;
+        };
+        let rom_offset = file_offset - self.header;
+        steps.push(match self.map {
+            SnesMap::HiRom => format!("HiROM: (bank & 3F) << 16 | offset = ROM {rom_offset:X}"),
+            SnesMap::ExHiRom => format!("ExHiROM: {}(bank & 3F) << 16 | offset = ROM {rom_offset:X}", if bank & 0x80 == 0 { "400000 | " } else { "" }),
+            SnesMap::LoRom | SnesMap::Auto => format!("LoROM: (bank & 7F) << 15 | (offset & 7FFF) = ROM {rom_offset:X}"),
+        });
+        steps.push(match self.header {
+            0 => format!("no copier header: file offset {file_offset:X}"),
+            header => format!("{header:X} byte copier header: file offset {header:X} + {rom_offset:X} = {file_offset:X}"),
+        });
+        if file_offset >= self.size { steps.push(format!("the file is only {:X} bytes, so it isn't ROM", self.size)); }
+        steps
+    }
+}
+
+// how much the internal header at `base` looks like a real one for a map mode (the map byte's low nibble) in `modes`
+fn header_score<I: Image + ?Sized>(image: &I, base: u64, modes: &[u8]) -> io::Result<u8> {
+    let mut header = [0_u8; 0x20];
+    image.read_bytes(&mut header, base)?;
+
+    let complement = u16::from_le_bytes([header[0x1C], header[0x1D]]);
+    let checksum = u16::from_le_bytes([header[0x1E], header[0x1F]]);
+
+    let mut score = 0;
+    if complement ^ checksum == 0xFFFF { score += 2; }
+    if header[0x15] & 0xE0 == 0x20 && modes.contains(&(header[0x15] & 0x0F)) { score += 1; }
+    Ok(score)
+}
+
+pub struct Snes;
+
+impl Console for Snes {
+    fn decode(&self, code: &str) -> Result<Patch, CodeError> {
+        parse_snes(code)
+    }
+
+    fn expected_lengths(&self) -> &'static [usize] {
+        SNES_LENGTHS
+    }
+
+    fn alphabet(&self) -> &'static [char] {
+        &SNES_CONVERSION
+    }
+
+    fn format(&self) -> String {
+        format!("XXXX-XXXX, 8 of {}; never checked", SNES_CONVERSION.iter().collect::<String>())
+    }
+}
+
+pub fn parse_snes(code: &str) -> Result<Patch, CodeError> {
+    let digits: Vec<char> = normalize(code).chars().collect();
+    if digits.len() != 8 {
+        return Err(CodeError::BadLength { code: code.to_string(), length: digits.len(), expected: SNES_LENGTHS });
+    }
+
+    let mut data: u32 = 0;
+    for (index, &i) in digits.iter().enumerate() {
+        match SNES_CONVERSION.iter().position(|&c| c == i) {
+            Some(x) => data = (data << 4) | x as u32,
+            None => return Err(CodeError::InvalidChar { code: code.to_string(), index, found: i, expected: &SNES_CONVERSION }),
+        }
+    }
+
+    // value is the first byte, the address is the remaining 24 bits shuffled
+    /*
+        ijkl qrst opab cduv wxef ghmn
+        abcd efgh ijkl mnop qrst uvwx
+     */
+    let n = data & 0xFF_FFFF;
+    let address = ((n & 0x00_3C00) << 10) | ((n & 0x00_003C) << 14) | ((n & 0xF0_0000) >> 8) | ((n & 0x00_0003) << 10)
+        | ((n & 0x00_C000) >> 6) | ((n & 0x0F_0000) >> 12) | ((n & 0x00_03C0) >> 6);
+
+    // SNES codes carry no compare byte, they always write
+    Ok(Patch { address, value: vec![(data >> 24) as u8], compare: None })
+}
+
+/// Build the `XXXX-XXXX` code writing `patch.value` at `patch.address`, a
+/// 24-bit bus address as the CPU sees it (not a file offset); `None` when the
+/// address doesn't fit in 24 bits or the value isn't a single byte
+pub fn encode_snes(patch: &Patch) -> Option<String> {
+    let [value] = patch.value[..] else { return None };
+    let a = patch.address;
+    if a > 0xFF_FFFF { return None; }
+
+    // undo the decoder's shuffle, term by term
+    let n = ((a & 0xF0_0000) >> 10) | ((a & 0x0F_0000) >> 14) | ((a & 0x00_F000) << 8) | ((a & 0x00_0C00) >> 10)
+        | ((a & 0x00_0300) << 6) | ((a & 0x00_00F0) << 12) | ((a & 0x00_000F) << 6);
+    let data = ((value as u32) << 24) | n;
+
+    let chars: String = (0..8).rev().map(|i| SNES_CONVERSION[((data >> (i * 4)) & 0xF) as usize]).collect();
+    Some(format!("{}-{}", &chars[..4], &chars[4..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{patch::{apply, ApplyStatus}, testing::Rng};
+
+    const LOROM_512K: Layout = Layout { header: 0, map: SnesMap::LoRom, size: 0x8_0000 };
+
+    #[test]
+    fn codes_carry_no_compare_byte() {
+        assert_eq!(parse_snes("C264-64D7").unwrap(), Patch { address: 0x00_8E28, value: vec![0xAD], compare: None });
+    }
+
+    #[test]
+    fn points_at_a_character_outside_the_alphabet() {
+        let err = parse_snes("C264-H4D7").unwrap_err();
+        // the hyphen isn't counted
+        assert!(matches!(err, CodeError::InvalidChar { index: 4, found: 'H', .. }), "{err:?}");
+        assert_eq!(err.to_string(), "Invalid character 'H' at position 5 of C264-H4D7 (its digits are DF4709156BC8A23E for 0-F, not standard hex)");
+    }
+
+    #[test]
+    fn a_patch_always_writes_whatever_the_rom_holds() {
+        let patch = parse_snes("C264-64D7").unwrap();
+        for held in [0x00, 0x5A, 0xFF] {
+            let mut rom = vec![held; 0x1000];
+            let applied = apply(&mut rom, 0xE28, &patch, false).unwrap();
+            assert_eq!(applied.status, ApplyStatus::Applied);
+            assert_eq!(rom[0xE28], 0xAD);
+        }
+    }
+
+    #[test]
+    fn maps_lorom_and_hirom() {
+        assert_eq!(LOROM_512K.file_offset(0x00_8E28), Some(0x0E28));
+        assert_eq!(Layout { header: 0x200, ..LOROM_512K }.file_offset(0x00_8E28), Some(0x1028));
+        assert_eq!(Layout { map: SnesMap::HiRom, ..LOROM_512K }.file_offset(0xC0_1234), Some(0x1234));
+    }
+
+    #[test]
+    fn detects_and_maps_exhirom() {
+        // a 6MB dump with an ExHiROM internal header 4MB in: map mode 25 and a checksum pair that agrees
+        let mut rom = vec![0_u8; 0x60_0000];
+        rom[0x40_FFC0 + 0x15] = 0x25;
+        rom[0x40_FFC0 + 0x1C..0x40_FFC0 + 0x20].copy_from_slice(&[0xFF, 0xFF, 0x00, 0x00]);
+        let layout = Layout::detect(&rom[..], SnesMap::Auto).unwrap();
+        assert_eq!(layout, Layout { header: 0, map: SnesMap::ExHiRom, size: 0x60_0000 });
+
+        // $C0-$FF hold the first 4MB, $40-$7D the rest, and $00-$3F mirror the latter's upper halves
+        assert_eq!(layout.file_offset(0xC0_1234), Some(0x1234));
+        assert_eq!(layout.file_offset(0x40_1234), Some(0x40_1234));
+        assert_eq!(layout.file_offset(0x00_8E28), Some(0x40_8E28));
+        assert_eq!(layout.file_offset(0x7E_1234), None);
+    }
+
+    #[test]
+    fn an_address_past_the_end_of_the_file_isnt_rom() {
+        // CC6D-FA70 writes bank $7D, 4MB into a LoROM map
+        let patch = parse_snes("CC6D-FA70").unwrap();
+        assert_eq!(patch.address, 0x7D_8000);
+        assert_eq!(LOROM_512K.file_offset(patch.address), None);
+        assert_eq!(Layout { size: 4, ..LOROM_512K }.file_offset(0x00_8E28), None);
+        // the last byte of the file still is
+        assert_eq!(LOROM_512K.file_offset(0x0F_FFFF), Some(0x7_FFFF));
+    }
+
+    #[test]
+    fn random_patches_round_trip() {
+        let mut rng = Rng::new(0x534E);
+        for _ in 0..2000 {
+            let patch = Patch { address: rng.below(0x100_0000) as u32, value: vec![rng.byte()], compare: None };
+            let code = encode_snes(&patch).unwrap();
+            assert_eq!(parse_snes(&code).unwrap(), patch, "{code}");
+        }
+        // 24-bit bus addresses, no wider
+        assert_eq!(encode_snes(&Patch { address: 0x100_0000, value: vec![0], compare: None }), None);
+    }
+}
@@ -0,0 +1,88 @@
+//! Every system's code alphabet, code lengths and address limits in one
+//! place, for tools that build or check codes themselves. Each is defined
+//! next to the decoder that uses it; these are the stable names to reach
+//! them by.
+//!
+//! An alphabet lists a system's characters in value order, so a character's
+//! index is the 4 (or 5, for Genesis) bits it stands for.
+
+use crate::mode::Mode;
+
+pub use crate::codes::HEX_DIGITS;
+pub use crate::gameboy::GAMEBOY_LENGTHS;
+pub use crate::genesis::{GENESIS_CONVERSION, GENESIS_FOURTH, GENESIS_LENGTHS};
+pub use crate::nes::{NES_CONVERSION, NES_LENGTHS};
+pub use crate::snes::{SNES_CONVERSION, SNES_LENGTHS};
+
+/// Game Boy and Game Gear codes are written in plain hex
+pub const GAMEBOY_CONVERSION: [char; 16] = HEX_DIGITS;
+
+/// First CPU address past Game Boy cartridge ROM (`$0000-$7FFF`)
+pub const GAMEBOY_ROM_END: u32 = crate::gameboy::Layout::GAMEBOY_ROM_END;
+/// First CPU address past the Game Gear's mapper slots (`$0000-$BFFF`)
+pub const GAMEGEAR_ROM_END: u32 = crate::gameboy::Layout::GAMEGEAR_ROM_END;
+/// Highest address a NES code reaches, relative to `$8000` as decoded
+pub const NES_MAX_ADDRESS: u32 = 0x7FFF;
+/// Highest address a SNES code reaches, a 24-bit bus address
+pub const SNES_MAX_ADDRESS: u32 = 0xFF_FFFF;
+/// Highest address a Genesis code reaches: the last word of 4MB of cartridge ROM
+pub const GENESIS_MAX_ADDRESS: u32 = 0x3F_FFFE;
+/// Highest address a Game Boy or Game Gear code reaches, a 16-bit CPU address
+pub const GAMEBOY_MAX_ADDRESS: u32 = 0xFFFF;
+
+/// The highest address a `mode` code can decode to, if its codes are supported
+pub fn max_address(mode: Mode) -> Option<u32> {
+    match mode {
+        Mode::Nintendo => Some(NES_MAX_ADDRESS),
+        Mode::SuperNintendo => Some(SNES_MAX_ADDRESS),
+        Mode::GameBoy | Mode::GameGear => Some(GAMEBOY_MAX_ADDRESS),
+        Mode::Genesis => Some(GENESIS_MAX_ADDRESS),
+        Mode::MasterSystem => None,
+    }
+}
+
+/// Bytes a NES code writes
+pub const NES_VALUE_BYTES: usize = 1;
+/// Bytes a SNES code writes
+pub const SNES_VALUE_BYTES: usize = 1;
+/// Bytes a Game Boy or Game Gear code writes
+pub const GAMEBOY_VALUE_BYTES: usize = 1;
+/// Bytes a Genesis code writes: one big endian word
+pub const GENESIS_VALUE_BYTES: usize = 2;
+
+/// How many bytes a `mode` code writes, if its codes are supported
+pub fn value_bytes(mode: Mode) -> Option<usize> {
+    match mode {
+        Mode::Nintendo => Some(NES_VALUE_BYTES),
+        Mode::SuperNintendo => Some(SNES_VALUE_BYTES),
+        Mode::GameBoy | Mode::GameGear => Some(GAMEBOY_VALUE_BYTES),
+        Mode::Genesis => Some(GENESIS_VALUE_BYTES),
+        Mode::MasterSystem => None,
+    }
+}
+
+/// Whether `value` fits in one `mode` write
+pub fn fits(mode: Mode, value: u32) -> bool {
+    value_bytes(mode).is_some_and(|bytes| u64::from(value) < 1 << (8 * bytes))
+}
+
+/// Why `value` doesn't fit in a `mode` write, e.g. `value 0x1FF too large for NES byte write`
+pub fn too_wide(mode: Mode, value: u32) -> String {
+    let width = if value_bytes(mode) == Some(2) { "word" } else { "byte" };
+    format!("value {value:#X} too large for {mode} {width} write")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_past_a_systems_write_width_dont_fit() {
+        assert!(fits(Mode::Nintendo, 0xFF));
+        assert!(!fits(Mode::Nintendo, 0x1234));
+        assert_eq!(too_wide(Mode::Nintendo, 0x1234), "value 0x1234 too large for NES byte write");
+        assert!(fits(Mode::Genesis, 0xFFFF));
+        assert!(!fits(Mode::Genesis, 0x12_3456));
+        assert_eq!(too_wide(Mode::Genesis, 0x12_3456), "value 0x123456 too large for Genesis word write");
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by the unit tests.
+
+/// A small xorshift generator, so property tests draw the same cases on every run
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number below `end`
+    pub fn below(&mut self, end: u64) -> u64 {
+        self.next() % end
+    }
+
+    pub fn byte(&mut self) -> u8 {
+        self.next() as u8
+    }
+
+    /// A byte half the time, `None` the rest
+    pub fn maybe_byte(&mut self) -> Option<u8> {
+        (self.next() & 1 == 0).then(|| self.byte())
+    }
+}
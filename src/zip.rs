@@ -0,0 +1,339 @@
+//! Just enough of the zip format to patch a ROM that ships in an archive:
+//! reading stored and deflated entries, and writing a single stored entry
+//! back out. Zip64 and encrypted archives aren't supported.
+
+use std::io;
+
+use crate::checksum::crc32;
+
+const LOCAL_HEADER: u32 = 0x0403_4B50;
+const CENTRAL_HEADER: u32 = 0x0201_4B50;
+const END_OF_DIRECTORY: u32 = 0x0605_4B50;
+
+/// A file listed in an archive's central directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+    local_offset: usize,
+}
+
+impl Entry {
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// Whether `data` starts like a zip archive
+pub fn is_zip(data: &[u8]) -> bool {
+    data.starts_with(&LOCAL_HEADER.to_le_bytes()) || data.starts_with(&END_OF_DIRECTORY.to_le_bytes())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("bad zip archive: {message}"))
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<u16> {
+    data.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]])).ok_or_else(|| invalid("truncated"))
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<u32> {
+    data.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).ok_or_else(|| invalid("truncated"))
+}
+
+/// Every entry in the archive's central directory
+pub fn entries(data: &[u8]) -> io::Result<Vec<Entry>> {
+    // the end record sits in the last 22 bytes plus up to 64KB of comment
+    let search_from = data.len().saturating_sub(22 + 0xFFFF);
+    let end = (search_from..data.len().saturating_sub(21)).rev()
+        .find(|&at| u32_at(data, at).is_ok_and(|sig| sig == END_OF_DIRECTORY))
+        .ok_or_else(|| invalid("no end of central directory"))?;
+
+    let count = u16_at(data, end + 10)? as usize;
+    let mut at = u32_at(data, end + 16)? as usize;
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(data, at)? != CENTRAL_HEADER { return Err(invalid("bad central directory")); }
+        let name_len = u16_at(data, at + 28)? as usize;
+        let skip = u16_at(data, at + 30)? as usize + u16_at(data, at + 32)? as usize;
+        let name = data.get(at + 46..at + 46 + name_len).ok_or_else(|| invalid("truncated"))?;
+
+        let entry = Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(data, at + 10)?,
+            crc: u32_at(data, at + 16)?,
+            compressed_size: u32_at(data, at + 20)? as usize,
+            size: u32_at(data, at + 24)? as usize,
+            local_offset: u32_at(data, at + 42)? as usize,
+        };
+        if [entry.compressed_size, entry.size, entry.local_offset].contains(&(u32::MAX as usize)) {
+            return Err(invalid("zip64 archives aren't supported"));
+        }
+        entries.push(entry);
+        at += 46 + name_len + skip;
+    }
+    Ok(entries)
+}
+
+/// The uncompressed contents of `entry`, checked against its CRC
+pub fn read(data: &[u8], entry: &Entry) -> io::Result<Vec<u8>> {
+    let at = entry.local_offset;
+    if u32_at(data, at)? != LOCAL_HEADER { return Err(invalid("bad local header")); }
+    let start = at + 30 + u16_at(data, at + 26)? as usize + u16_at(data, at + 28)? as usize;
+    let compressed = data.get(start..start + entry.compressed_size).ok_or_else(|| invalid("truncated"))?;
+
+    let contents = match entry.method {
+        0 => compressed.to_vec(),
+        8 => inflate(compressed, entry.size)?,
+        method => return Err(invalid(&format!("compression method {method} isn't supported"))),
+    };
+    if contents.len() != entry.size || crc32(&contents) != entry.crc {
+        return Err(invalid(&format!("{} fails its CRC", entry.name)));
+    }
+    Ok(contents)
+}
+
+/// An archive holding `contents` as the single stored entry `name`
+pub fn write(name: &str, contents: &[u8]) -> Vec<u8> {
+    let (crc, size, name_len) = (crc32(contents), contents.len() as u32, name.len() as u16);
+    // version 2.0, no flags, stored, no timestamp
+    let common = |out: &mut Vec<u8>| {
+        for field in [20_u16, 0, 0, 0, 0] { out.extend(field.to_le_bytes()); }
+        for field in [crc, size, size] { out.extend(field.to_le_bytes()); }
+        out.extend(name_len.to_le_bytes());
+        out.extend(0_u16.to_le_bytes());
+    };
+
+    let mut out = Vec::with_capacity(contents.len() + 2 * name.len() + 98);
+    out.extend(LOCAL_HEADER.to_le_bytes());
+    common(&mut out);
+    out.extend(name.as_bytes());
+    out.extend(contents);
+
+    let directory = out.len() as u32;
+    out.extend(CENTRAL_HEADER.to_le_bytes());
+    out.extend(20_u16.to_le_bytes());
+    common(&mut out);
+    // comment length, disk, internal and external attributes, local header offset
+    for field in [0_u16, 0, 0] { out.extend(field.to_le_bytes()); }
+    for field in [0_u32, 0] { out.extend(field.to_le_bytes()); }
+    out.extend(name.as_bytes());
+
+    let directory_size = out.len() as u32 - directory;
+    out.extend(END_OF_DIRECTORY.to_le_bytes());
+    for field in [0_u16, 0, 1, 1] { out.extend(field.to_le_bytes()); }
+    for field in [directory_size, directory] { out.extend(field.to_le_bytes()); }
+    out.extend(0_u16.to_le_bytes());
+    out
+}
+
+// RFC 1951 deflate, decoded the same way zlib's puff does it
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or_else(|| invalid("deflate stream ends early"))?;
+            self.buf |= (byte as u32) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0_i32, 0_i32, 0_i32);
+        for &count in &huffman.counts[1..] {
+            code |= self.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first { return Ok(huffman.symbols[(index + code - first) as usize]); }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad Huffman code"))
+    }
+}
+
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0_u16; 16];
+        for &length in lengths { counts[length as usize] += 1; }
+        counts[0] = 0;
+
+        let mut offsets = [0_u16; 16];
+        for i in 1..15 { offsets[i + 1] = offsets[i] + counts[i]; }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// the order code length code lengths are sent in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// `size` is the entry's recorded size, which the output may not outgrow however the stream is
+// crafted; the allocation trusts it only as far as deflate's best ratio allows
+fn inflate(data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut bits = Bits { data, pos: 0, buf: 0, count: 0 };
+    let mut out = Vec::with_capacity(size.min(data.len().saturating_mul(1032)));
+
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                // stored: realign to a byte, then LEN and its complement
+                bits.buf = 0;
+                bits.count = 0;
+                let len = bits.bits(16)? as usize;
+                if bits.bits(16)? as usize != !len & 0xFFFF { return Err(invalid("bad stored block")); }
+                let block = data.get(bits.pos..bits.pos + len).ok_or_else(|| invalid("deflate stream ends early"))?;
+                if out.len() + len > size { return Err(too_long()); }
+                out.extend_from_slice(block);
+                bits.pos += len;
+            },
+            1 => {
+                let mut lengths = [0_u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                inflate_block(&mut bits, &mut out, size, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            },
+            2 => {
+                let literals = bits.bits(5)? as usize + 257;
+                let distances = bits.bits(5)? as usize + 1;
+                let code_lengths = bits.bits(4)? as usize + 4;
+
+                let mut lengths = [0_u8; 19];
+                for &i in &CODE_LENGTH_ORDER[..code_lengths] { lengths[i] = bits.bits(3)? as u8; }
+                let code_length_codes = Huffman::new(&lengths);
+
+                let mut lengths = vec![0_u8; literals + distances];
+                let mut i = 0;
+                while i < lengths.len() {
+                    let (value, repeat) = match bits.decode(&code_length_codes)? {
+                        symbol @ 0..=15 => (symbol as u8, 1),
+                        16 if i > 0 => (lengths[i - 1], 3 + bits.bits(2)? as usize),
+                        17 => (0, 3 + bits.bits(3)? as usize),
+                        18 => (0, 11 + bits.bits(7)? as usize),
+                        _ => return Err(invalid("bad code lengths")),
+                    };
+                    let run = lengths.get_mut(i..i + repeat).ok_or_else(|| invalid("bad code lengths"))?;
+                    run.fill(value);
+                    i += repeat;
+                }
+
+                let (literal_lengths, distance_lengths) = lengths.split_at(literals);
+                inflate_block(&mut bits, &mut out, size, &Huffman::new(literal_lengths), &Huffman::new(distance_lengths))?;
+            },
+            _ => return Err(invalid("bad block type")),
+        }
+        if last { return Ok(out); }
+    }
+}
+
+fn too_long() -> io::Error {
+    invalid("an entry inflates past its recorded size")
+}
+
+fn inflate_block(bits: &mut Bits, out: &mut Vec<u8>, size: usize, literals: &Huffman, distances: &Huffman) -> io::Result<()> {
+    loop {
+        match bits.decode(literals)? {
+            0..=255 if out.len() == size => return Err(too_long()),
+            symbol @ 0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            symbol => {
+                let symbol = symbol as usize - 257;
+                let (Some(&base), Some(&extra)) = (LENGTH_BASE.get(symbol), LENGTH_EXTRA.get(symbol)) else { return Err(invalid("bad length")) };
+                let length = base as usize + bits.bits(extra as u32)? as usize;
+
+                let symbol = bits.decode(distances)? as usize;
+                let (Some(&base), Some(&extra)) = (DISTANCE_BASE.get(symbol), DISTANCE_EXTRA.get(symbol)) else { return Err(invalid("bad distance")) };
+                let distance = base as usize + bits.bits(extra as u32)? as usize;
+                if distance > out.len() { return Err(invalid("distance too far back")); }
+                if out.len() + length > size { return Err(too_long()); }
+
+                // byte by byte, since a match may overlap the bytes it's producing
+                let from = out.len() - distance;
+                for i in 0..length { out.push(out[from + i]); }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // game.nes holding b"RGGP" * 64 then bytes 0..32, deflated by Python's zipfile
+    const DEFLATED: [u8; 155] = [
+        0x50, 0x4B, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0xAB, 0x54, 0x4E, 0x5D, 0xCF, 0x14, 0xA5, 0x71, 0x29, 0x00,
+        0x00, 0x00, 0x20, 0x01, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x67, 0x61, 0x6D, 0x65, 0x2E, 0x6E, 0x65, 0x73, 0x0B, 0x72,
+        0x77, 0x0F, 0x08, 0x1A, 0xC1, 0x98, 0x81, 0x91, 0x89, 0x99, 0x85, 0x95, 0x8D, 0x9D, 0x83, 0x93, 0x8B, 0x9B, 0x87, 0x97,
+        0x8F, 0x5F, 0x40, 0x50, 0x48, 0x58, 0x44, 0x54, 0x4C, 0x5C, 0x42, 0x52, 0x4A, 0x5A, 0x46, 0x56, 0x4E, 0x1E, 0x00, 0x50,
+        0x4B, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0xAB, 0x54, 0x4E, 0x5D, 0xCF, 0x14, 0xA5, 0x71, 0x29,
+        0x00, 0x00, 0x00, 0x20, 0x01, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x67, 0x61, 0x6D, 0x65, 0x2E, 0x6E, 0x65, 0x73, 0x50, 0x4B, 0x05, 0x06, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x36, 0x00, 0x00, 0x00, 0x4F, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn contents() -> Vec<u8> {
+        b"RGGP".repeat(64).into_iter().chain(0..32).collect()
+    }
+
+    #[test]
+    fn stored_entries_round_trip() {
+        let archive = write("game.sfc", &contents());
+        assert!(is_zip(&archive));
+        let entries = entries(&archive).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "game.sfc");
+        assert_eq!(read(&archive, &entries[0]).unwrap(), contents());
+    }
+
+    #[test]
+    fn reads_deflated_entries() {
+        let entries = entries(&DEFLATED).unwrap();
+        assert_eq!(entries[0].name, "game.nes");
+        assert_eq!(entries[0].method, 8);
+        assert_eq!(read(&DEFLATED, &entries[0]).unwrap(), contents());
+    }
+
+    #[test]
+    fn stops_inflating_past_the_recorded_size() {
+        let mut entry = entries(&DEFLATED).unwrap().remove(0);
+        for size in [0, 1, 16, contents().len() - 1] {
+            entry.size = size;
+            let error = read(&DEFLATED, &entry).unwrap_err();
+            assert!(error.to_string().contains("past its recorded size"), "{size}: {error}");
+        }
+    }
+}
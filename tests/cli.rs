@@ -0,0 +1,330 @@
+//! The rggp binary run as a user would, against ROMs built in a scratch directory.
+
+use std::{fs, path::PathBuf, process::{Command, Output}};
+
+// a fresh scratch directory for one test
+fn scratch(test: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("cli").join(test);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn rggp(args: &[&str]) -> Output {
+    rggp_with_env(args, &[])
+}
+
+fn rggp_with_env(args: &[&str], vars: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rggp"));
+    for var in ["RUST_LOG", "RGGP_CODES", "RGGP_MODE", "RGGP_INPUT", "RGGP_OUTPUT"] { command.env_remove(var); }
+    command.args(args).envs(vars.iter().copied()).output().unwrap()
+}
+
+// an iNES header declaring two 16KB PRG banks and no CHR, then 32KB of PRG
+fn nes_rom() -> Vec<u8> {
+    let mut rom = vec![0xEA_u8; 0x10 + 0x8000];
+    rom[..16].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    rom
+}
+
+#[test]
+fn patches_in_place_when_input_is_output() {
+    let dir = scratch("patches_in_place_when_input_is_output");
+    let rom = dir.join("same.nes");
+    fs::write(&rom, nes_rom()).unwrap();
+    let path = rom.to_str().unwrap();
+
+    let output = rggp(&["SXIOPO", "nes", path, path]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let patched = fs::read(&rom).unwrap();
+    assert_eq!(patched.len(), 0x8010);
+    assert_eq!(patched[0x11E9], 0xAD);
+}
+
+#[test]
+fn a_bad_code_leaves_an_in_place_input_untouched() {
+    let dir = scratch("a_bad_code_leaves_an_in_place_input_untouched");
+    let rom = dir.join("same.nes");
+    fs::write(&rom, nes_rom()).unwrap();
+    let path = rom.to_str().unwrap();
+
+    let output = rggp(&["SXIOPO+BBBBBB", "nes", path, path]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(fs::read(&rom).unwrap(), nes_rom());
+}
+
+#[test]
+fn a_buffer_is_patched_where_a_headerless_file_would_be() {
+    // a 16 byte header's worth of zeros, then 16 bytes of PRG
+    let buffer = "00".repeat(0x20);
+    let output = rggp(&["NES:8002=AD", "nes", "--buffer", &buffer]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let expected = format!("{}AD{}\n", "00".repeat(0x12), "00".repeat(0x0D));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
+
+#[test]
+fn a_buffer_never_grows_to_fit_a_code() {
+    let output = rggp(&["SXIOPO", "nes", "--buffer", &"00".repeat(0x20)]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn strict_batches_fail_only_the_files_that_warn() {
+    let dir = scratch("strict_batches_fail_only_the_files_that_warn");
+    let (roms, out) = (dir.join("roms"), dir.join("out"));
+    fs::create_dir(&roms).unwrap();
+    // sorted first, so its warning comes before the clean files are patched
+    let mut overdump = nes_rom();
+    overdump.extend([0xEA; 0x4000]);
+    fs::write(roms.join("a.nes"), overdump).unwrap();
+    for name in ["b.nes", "c.nes", "d.nes"] { fs::write(roms.join(name), nes_rom()).unwrap(); }
+
+    let output = rggp(&["--strict", "SXIOPO", "nes", roms.to_str().unwrap(), out.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(6), "{}", String::from_utf8_lossy(&output.stderr));
+    let report = String::from_utf8_lossy(&output.stdout);
+    let statuses: Vec<_> = report.lines().map(|line| line.split('\t').take(2).collect::<Vec<_>>().join(" ")).collect();
+    assert_eq!(statuses, ["a.nes failed", "b.nes ok", "c.nes ok", "d.nes ok"]);
+}
+
+#[test]
+fn table_flags_codes_whose_bytes_overlap() {
+    let output = rggp(&["table", "0x8000=AD,BE,EF+0x8002=00+0x8003=01", "nes"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let table = String::from_utf8_lossy(&output.stdout);
+    let overlapping: Vec<_> = table.lines().skip(1).map(|line| line.ends_with("overlaps")).collect();
+    assert_eq!(overlapping, [true, true, false]);
+}
+
+#[test]
+fn exec_refuses_a_buffer_and_a_configured_stdout() {
+    let output = rggp(&["--buffer", "0000", "--exec", "true", "SXIOPO", "nes"]);
+    assert_eq!(output.status.code(), Some(2));
+
+    let dir = scratch("exec_refuses_a_buffer_and_a_configured_stdout");
+    let (rom, config) = (dir.join("game.nes"), dir.join("run.toml"));
+    fs::write(&rom, nes_rom()).unwrap();
+    fs::write(&config, "stdout = true\nexec = \"true\"\n").unwrap();
+    let output = rggp(&["--config", config.to_str().unwrap(), "SXIOPO", "nes", rom.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn a_manifest_remakes_its_output_with_guards_and_force() {
+    let dir = scratch("a_manifest_remakes_its_output_with_guards_and_force");
+    let (rom, out, copy) = (dir.join("game.nes"), dir.join("out.nes"), dir.join("copy.nes"));
+    fs::write(&rom, nes_rom()).unwrap();
+
+    // ATAPAA writes 60 at $9000, guarded by SXSOPOXV's compare byte, EA at $91D9
+    let output = rggp(&["--manifest", "--force", "ataPAA?SXSOPOXV", "nes", rom.to_str().unwrap(), out.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let manifest = dir.join("out.nes.manifest.toml");
+    let written = fs::read_to_string(&manifest).unwrap();
+    assert!(written.contains("\"ATAPAA?SXSOPOXV\","), "{written}");
+    assert!(written.contains("\nforce = true\n"), "{written}");
+    assert_eq!(fs::read(&out).unwrap()[0x1010], 0x60);
+
+    let output = rggp(&["--config", manifest.to_str().unwrap(), "-o", copy.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(fs::read(&copy).unwrap(), fs::read(&out).unwrap());
+}
+
+#[test]
+fn a_configured_dry_run_refuses_a_manifest() {
+    let dir = scratch("a_configured_dry_run_refuses_a_manifest");
+    let (rom, config) = (dir.join("game.nes"), dir.join("run.toml"));
+    fs::write(&rom, nes_rom()).unwrap();
+    fs::write(&config, "dry-run = true\nmanifest = true\n").unwrap();
+    let output = rggp(&["--config", config.to_str().unwrap(), "SXIOPO", "nes", rom.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn environment_variables_fill_in_the_positionals_left_off() {
+    let dir = scratch("environment_variables_fill_in_the_positionals_left_off");
+    let rom = dir.join("game.nes");
+    fs::write(&rom, nes_rom()).unwrap();
+    let (input, output) = (rom.to_str().unwrap(), dir.join("out.nes"));
+    let output = output.to_str().unwrap();
+
+    let patches = |args: &[&str], vars: &[(&str, &str)]| {
+        let _ = fs::remove_file(output);
+        let run = rggp_with_env(args, vars);
+        assert!(run.status.success(), "{args:?} {vars:?}: {}", String::from_utf8_lossy(&run.stderr));
+        assert_eq!(fs::read(output).unwrap()[0x11E9], 0xAD, "{args:?} {vars:?}");
+    };
+    patches(&["SXIOPO", input, output], &[("RGGP_MODE", "nes")]);
+    patches(&["SXIOPO", "nes", output], &[("RGGP_INPUT", input)]);
+    patches(&[input, output], &[("RGGP_CODES", "SXIOPO"), ("RGGP_MODE", "nes")]);
+    patches(&["SXIOPO", "nes", input], &[("RGGP_OUTPUT", output)]);
+    // the command line still wins, through the option the variable's positional became
+    patches(&["--mode", "nes", "SXIOPO", input, output], &[("RGGP_MODE", "gb")]);
+}
+
+#[test]
+fn pad_lets_a_code_past_the_end_of_the_rom_land() {
+    let dir = scratch("pad_lets_a_code_past_the_end_of_the_rom_land");
+    let (rom, out) = (dir.join("short.bin"), dir.join("out.bin"));
+    let mut short = vec![0xFF_u8; 0x8000];
+    short[0x100..0x104].copy_from_slice(b"SEGA");
+    fs::write(&rom, &short).unwrap();
+    let (input, output) = (rom.to_str().unwrap(), out.to_str().unwrap());
+
+    // SCRA-BJX0 writes 5478 at $009C76, past the end of 32KB
+    let run = rggp(&["SCRA-BJX0", "genesis", input, output]);
+    assert_eq!(run.status.code(), Some(5), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(!out.exists());
+
+    let run = rggp(&["--pad", "64K", "SCRA-BJX0", "genesis", input, output]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    let patched = fs::read(&out).unwrap();
+    assert_eq!(patched.len(), 0x10000);
+    assert_eq!(patched[..0x8000], short[..]);
+    assert_eq!(patched[0x9C76..0x9C78], [0x54, 0x78]);
+    // the rest of the padding is zeros
+    assert!(patched[0x8000..0x9C76].iter().chain(&patched[0x9C78..]).all(|&byte| byte == 0));
+    assert_eq!(fs::read(&rom).unwrap(), short);
+}
+
+// 64KB of Genesis ROM, big endian, with SEGA at 0x100
+fn genesis_rom() -> Vec<u8> {
+    let mut rom: Vec<u8> = (0..0x10000_u32).map(|i| i as u8).collect();
+    rom[0x100..0x104].copy_from_slice(b"SEGA");
+    rom
+}
+
+#[test]
+fn md_outputs_are_byte_swapped_and_bin_outputs_arent() {
+    let dir = scratch("md_outputs_are_byte_swapped_and_bin_outputs_arent");
+    let (bin, md) = (dir.join("game.bin"), dir.join("game.md"));
+    let mut swapped = genesis_rom();
+    swapped.chunks_exact_mut(2).for_each(|word| word.swap(0, 1));
+    fs::write(&bin, genesis_rom()).unwrap();
+    fs::write(&md, &swapped).unwrap();
+
+    // SCRA-BJX0 writes the word 5478 at $009C76, whichever order INPUT is in
+    for input in [&bin, &md] {
+        let (out_bin, out_md) = (dir.join("out.bin"), dir.join("out.md"));
+        let run = rggp(&["SCRA-BJX0", "genesis", input.to_str().unwrap(), out_bin.to_str().unwrap(), "-o", out_md.to_str().unwrap()]);
+        assert!(run.status.success(), "{input:?}: {}", String::from_utf8_lossy(&run.stderr));
+
+        let (out_bin, out_md) = (fs::read(&out_bin).unwrap(), fs::read(&out_md).unwrap());
+        assert_eq!(out_bin[0x9C76..0x9C78], [0x54, 0x78], "{input:?}");
+        assert_eq!(out_md[0x9C76..0x9C78], [0x78, 0x54], "{input:?}");
+        assert_eq!((&out_bin[0x100..0x104], &out_md[0x100..0x104]), (&b"SEGA"[..], &b"ESAG"[..]), "{input:?}");
+        // nothing but the word changed
+        let mut expected = genesis_rom();
+        expected[0x9C76..0x9C78].copy_from_slice(&[0x54, 0x78]);
+        assert_eq!(out_bin, expected, "{input:?}");
+    }
+}
+
+#[test]
+fn repeated_code_flags_apply_in_order() {
+    let dir = scratch("repeated_code_flags_apply_in_order");
+    let (rom, out) = (dir.join("game.nes"), dir.join("out.nes"));
+    fs::write(&rom, nes_rom()).unwrap();
+
+    // the third code writes over the first, so the order shows in the ROM as well as the summary
+    let run = rggp(&["--csv", "--code", "8000=01", "--code", "8001=02", "--code", "8000=03", "nes", rom.to_str().unwrap(), out.to_str().unwrap()]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    let summary = String::from_utf8_lossy(&run.stdout);
+    let codes: Vec<_> = summary.lines().skip(1).filter(|line| !line.starts_with('#')).map(|line| line.split(',').next().unwrap()).collect();
+    assert_eq!(codes, ["8000=01", "8001=02", "8000=03"]);
+    assert_eq!(fs::read(&out).unwrap()[0x10..0x12], [0x03, 0x02]);
+}
+
+#[test]
+fn convert_goes_both_ways_for_nes_and_game_boy() {
+    let dir = scratch("convert_goes_both_ways_for_nes_and_game_boy");
+    let (nes, gb) = (dir.join("game.nes"), dir.join("game.gb"));
+    fs::write(&nes, nes_rom()).unwrap();
+    // 32KB of Game Boy ROM holding C8 at $4A17
+    let mut rom = vec![0_u8; 0x8000];
+    rom[0x4A17] = 0xC8;
+    fs::write(&gb, rom).unwrap();
+
+    let converts = |args: &[&str], expected: &str| {
+        let run = rggp(&[&["convert"], args].concat());
+        assert!(run.status.success(), "{args:?}: {}", String::from_utf8_lossy(&run.stderr));
+        assert_eq!(String::from_utf8_lossy(&run.stdout), expected, "{args:?}");
+    };
+    // the long form takes its compare byte from INPUT: EA at $91D9, C8 at $4A17
+    converts(&["SXIOPO", "nes", nes.to_str().unwrap()], "SXIOPO\tSXSOPOXV\n");
+    converts(&["SXSOPOXV", "nes"], "SXSOPOXV\tSXIOPO\n");
+    converts(&["00A-17B", "gb", gb.to_str().unwrap()], "00A-17B\t00A-17B-C49\n");
+    converts(&["00A-17B-C49", "gb"], "00A-17B-C49\t00A-17B\n");
+
+    // with nothing to read a compare byte from, a short code can't be lengthened
+    let run = rggp(&["convert", "--to", "long", "SXIOPO", "nes"]);
+    assert_eq!(run.status.code(), Some(3));
+}
+
+#[test]
+fn a_compare_mismatch_is_skipped_unless_forced() {
+    let dir = scratch("a_compare_mismatch_is_skipped_unless_forced");
+    let (rom, out) = (dir.join("game.nes"), dir.join("out.nes"));
+    fs::write(&rom, nes_rom()).unwrap();
+    let (input, output) = (rom.to_str().unwrap(), out.to_str().unwrap());
+    let status = |run: &Output| String::from_utf8_lossy(&run.stdout).lines().nth(1).unwrap().split(',').nth(6).unwrap().to_string();
+
+    // SXSOPOZS writes AD at $91D9 only where the ROM holds 5A, which this one doesn't
+    let run = rggp(&["--csv", "SXSOPOZS", "nes", input, output]);
+    assert_eq!(run.status.code(), Some(5), "{}", String::from_utf8_lossy(&run.stderr));
+    assert_eq!(status(&run), "skipped-compare-mismatch");
+    assert_eq!(fs::read(&out).unwrap(), nes_rom());
+    assert_eq!(fs::read(&rom).unwrap(), nes_rom());
+
+    let run = rggp(&["--csv", "--force", "SXSOPOZS", "nes", input, output]);
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert_eq!(status(&run), "forced");
+    assert_eq!(fs::read(&out).unwrap()[0x11E9], 0xAD);
+}
+
+#[test]
+fn master_system_and_game_gear_ram_codes_are_refused() {
+    let dir = scratch("master_system_and_game_gear_ram_codes_are_refused");
+    let (rom, out) = (dir.join("game.gg"), dir.join("out.gg"));
+    fs::write(&rom, vec![0_u8; 0x10000]).unwrap();
+    let (input, output) = (rom.to_str().unwrap(), out.to_str().unwrap());
+
+    let run = rggp(&["--force", "AAA-AAA", "sms", input, output]);
+    assert_eq!(run.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Master System codes aren't supported"), "{}", String::from_utf8_lossy(&run.stderr));
+
+    // 001-233 writes Game Gear work RAM at $C123, which no file offset holds
+    let run = rggp(&["001-233", "gg", input, output]);
+    assert_eq!(run.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("writes Game Gear RAM at C123"), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(!out.exists());
+}
+
+#[test]
+fn a_prg_size_that_doesnt_fit_the_file_warns_and_fails_strict_runs() {
+    let dir = scratch("a_prg_size_that_doesnt_fit_the_file_warns_and_fails_strict_runs");
+    let out = dir.join("out.nes");
+    // the header declares 32KB of PRG; one dump is 4KB over, the other cut 8KB short
+    let mut overdump = nes_rom();
+    overdump.extend([0xEA; 0x1000]);
+    let mut truncated = nes_rom();
+    truncated.truncate(0x10 + 0x6000);
+
+    for (name, rom, actual) in [("over.nes", overdump, "0x9010"), ("short.nes", truncated, "0x6010")] {
+        let rom_path = dir.join(name);
+        fs::write(&rom_path, &rom).unwrap();
+        let (input, output) = (rom_path.to_str().unwrap(), out.to_str().unwrap());
+
+        let run = rggp(&["SXIOPO", "nes", input, output]);
+        assert!(run.status.success(), "{name}: {}", String::from_utf8_lossy(&run.stderr));
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert!(stderr.contains(&format!("declares 0x8000 bytes of PRG and 0x0 of CHR after 0x10 of header, 0x8010 in all, but the file is {actual} bytes")), "{name}: {stderr}");
+        assert_eq!(fs::read(&out).unwrap()[0x11E9], 0xAD, "{name}");
+
+        fs::remove_file(&out).unwrap();
+        let run = rggp(&["--strict", "SXIOPO", "nes", input, output]);
+        assert_eq!(run.status.code(), Some(6), "{name}");
+        assert!(!out.exists(), "{name}");
+    }
+}
@@ -0,0 +1,45 @@
+//! A NES code run through the whole library pipeline: decoded, mapped past
+//! the iNES header, written into a ROM and read back.
+
+use rggp::{locate::{apply_codes, Locator}, mode::Mode, patch::ApplyStatus, rom::Image, snes::SnesMap};
+
+// an iNES header declaring two 16KB PRG banks and no CHR, then 32KB of PRG
+// filled with a byte no test code writes
+fn rom_32k() -> Vec<u8> {
+    let mut rom = vec![0xEA_u8; 0x10 + 0x8000];
+    rom[..16].copy_from_slice(&[b'N', b'E', b'S', 0x1A, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    rom
+}
+
+#[test]
+fn six_letter_code_lands_at_its_documented_offset() {
+    let mut rom = rom_32k();
+    let original = rom.clone();
+    let mut locator = Locator::new(Mode::Nintendo, SnesMap::Auto, None);
+
+    // SXIOPO is Super Mario Bros.' infinite lives: AD at $91D9
+    let results = apply_codes(&mut locator, &mut rom, &["SXIOPO"], false).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].status, ApplyStatus::Applied);
+    assert_eq!(results[0].address, 0x11D9);
+    assert_eq!(results[0].file_offset, 0x10 + 0x11D9);
+
+    let mut byte = [0_u8; 1];
+    rom.read_bytes(&mut byte, 0x11E9).unwrap();
+    assert_eq!(byte, [0xAD]);
+    // nothing else moved
+    let changed: Vec<usize> = rom.iter().zip(&original).enumerate().filter(|(_, (a, b))| a != b).map(|(i, _)| i).collect();
+    assert_eq!(changed, [0x11E9]);
+}
+
+#[test]
+fn a_second_run_of_the_same_code_lands_in_the_same_place() {
+    let mut rom = rom_32k();
+    let mut locator = Locator::new(Mode::Nintendo, SnesMap::Auto, None);
+
+    // no running offset is carried from one code to the next
+    let results = apply_codes(&mut locator, &mut rom, &["SXIOPO", "SXIOPO"], false).unwrap();
+    assert_eq!(results.iter().map(|result| result.file_offset).collect::<Vec<_>>(), [0x11E9, 0x11E9]);
+    assert_eq!(results[1].status, ApplyStatus::SkippedNoop);
+    assert_eq!(rom.len(), 0x10 + 0x8000);
+}